@@ -7,6 +7,11 @@ extern crate maplit;
 extern crate debug_here;
 
 use imtui;
+use imtui::Backend;
+use imtui::focus::{FocusEntry, FocusRing};
+use imtui::layout::{Constraint, Direction, Layout, Rect};
+use imtui::panes::{PaneChange, SplitPanes};
+use imtui::widgets::{ListEvent, ListView};
 use std::collections::HashSet;
 use futures::future;
 use futures::stream::{self, Stream, StreamExt};
@@ -40,7 +45,6 @@ use serde::{Deserialize};
 use serde_json::Value;
 use timeago;
 use log;
-use env_logger;
 
 #[derive(VariantCount, PartialEq, Eq, Hash, Clone)]
 enum WindowContent {
@@ -73,11 +77,11 @@ struct WindowData {
     window_content: WindowContent,
     hn_state: Rc<RefCell<HnState>>,
     active: bool,
-    show_comments: bool,
-    selected_story_id: Option<HnItemId>,
-    hovered_story_id: Option<HnItemId>,
+    /// Set while the window shows `selected_story_id` instead of the list.
+    show_comments: Cell<bool>,
+    selected_story_id: Cell<Option<HnItemId>>,
     hovered_comment_id: Option<HnItemId>,
-    max_stories: Cell<u32>,
+    list: RefCell<ListView>,
 }
 
 impl WindowData {
@@ -87,11 +91,10 @@ impl WindowData {
             window_content: window_content,
             hn_state: Rc::clone(hn_state),
             active: false,
-            show_comments: false,
-            selected_story_id: None,
-            hovered_story_id: None,
+            show_comments: Cell::new(false),
+            selected_story_id: Cell::new(None),
             hovered_comment_id: None,
-            max_stories: Cell::new(10),
+            list: RefCell::new(ListView::new()),
         }
     }
 
@@ -99,6 +102,50 @@ impl WindowData {
         self.active = active;
     }
 
+    fn story_ids<'a>(&self, refresh: &'a HnRefreshResult) -> &'a Vec<HnItemId> {
+        match self.window_content {
+            WindowContent::Top => &refresh.top_ids,
+            WindowContent::Show => &refresh.show_ids,
+            WindowContent::Ask => &refresh.ask_ids,
+            WindowContent::New => &refresh.new_ids,
+        }
+    }
+
+    /// Moves the selection in the story list, or goes back to it from a
+    /// story, while the window has the focus.
+    fn handle_events(&self, events: &[imtui::Event]) {
+        if self.show_comments.get() {
+            let back = events.iter().any(|event| match event {
+                imtui::Event::Key(key) => match key.key {
+                    imtui::Key::Escape | imtui::Key::Backspace | imtui::Key::Char('h') => true,
+                    _ => false,
+                },
+                _ => false,
+            });
+            if back {
+                self.show_comments.set(false);
+            }
+            return;
+        }
+        let hn_state = self.hn_state.borrow();
+        if let Some(last_list_refresh) = &hn_state.last_list_refresh {
+            let story_ids = self.story_ids(last_list_refresh);
+            let list_events = self.list.borrow_mut().handle_events(events, story_ids.len());
+            self.open_activated(&list_events, story_ids);
+        }
+    }
+
+    fn open_activated(&self, list_events: &[ListEvent], story_ids: &[HnItemId]) {
+        for event in list_events {
+            if let ListEvent::Activated(index) = event {
+                if let Some(&story_id) = story_ids.get(*index) {
+                    self.selected_story_id.set(Some(story_id));
+                    self.show_comments.set(true);
+                }
+            }
+        }
+    }
+
     fn render(&self, state: &AppState, draw_context: &DrawContext, pos: &(f32, f32), size: &(f32, f32)) {
         let title = imgui::ImString::new(&self.title);
         let window = imgui::Window::new(&title)
@@ -113,8 +160,8 @@ impl WindowData {
             draw_context.ui.text("");
 
             // Draw a specific story or draw the index
-            if self.show_comments {
-                self.render_single_story(draw_context, &window_token, pos, size);
+            if self.show_comments.get() {
+                self.render_single_story(draw_context);
             } else {
                 self.render_index(state, draw_context, &window_token, pos, size);
             }
@@ -130,90 +177,120 @@ impl WindowData {
         }
 
         let last_list_refresh = hn_state.last_list_refresh.as_ref().unwrap();
-        let story_ids = match self.window_content {
-            WindowContent::Top => &last_list_refresh.top_ids,
-            WindowContent::Show => &last_list_refresh.show_ids,
-            WindowContent::Ask => &last_list_refresh.ask_ids,
-            WindowContent::New => &last_list_refresh.new_ids,
-        };
-
-        let num_to_show = (self.max_stories.get() as usize).min(story_ids.len());
-        let ui = &draw_context.ui;
-        for (i, story_id) in story_ids[..num_to_show].iter().enumerate() {
+        let story_ids = self.story_ids(last_list_refresh);
+        let items = hn_state.items.borrow();
+        let item_height = if state.view_mode == StoryListViewMode::Micro { 1 } else { 2 };
+
+        let mut list = self.list.borrow_mut();
+        list.highlight = self.active;
+        let width = draw_context.ui.content_region_avail()[0].max(0.0) as usize;
+        let spacing = draw_context.ui.clone_style().item_spacing[0].max(0.0) as usize;
+        let list_events = list.draw(draw_context.ui, story_ids.len(), |_| item_height, |ui, i, _| {
+            let story_id = story_ids[i];
             // TODO: draw jobs too?
-            if let Some(story) = match hn_state.items.borrow().get(story_id) {
-                Some(item) => match item {
-                    HnItem::Story(story) => Some(story),
-                    _ => None,
-                },
-                None => {
-                    hn_state.items_to_fetch.queue_item(*story_id);
-                    None
+            let story = match items.get(&story_id) {
+                Some(HnItem::Story(story)) => story,
+                Some(_) => {
+                    ui.text_disabled(format!("{:2}.", i + 1));
+                    return;
                 }
-            } {
-                let color_stack = match self.hovered_story_id {
-                    Some(hovered_story_id) => {
-                        if self.active && hovered_story_id == *story_id {
-                            let style = ui.clone_style();
-                            let text_color = ui.push_style_color(
-                                imgui::StyleColor::Text,
-                                style[imgui::StyleColor::WindowBg]
-                            );
-                            let background_color = ui.push_style_color(
-                                imgui::StyleColor::WindowBg,
-                                style[imgui::StyleColor::Text]
-                            );
-
-                            let mut p0 = ui.cursor_screen_pos();
-                            p0[0] = p0[0] + 1.0;
-                            let mut p1 = p0;
-                            p1[0] = p1[0] + ui.calc_text_size(&imgui::ImString::new(&story.title), false, -1.0)[0] + 4.0;
-
-                            let draw_list = ui.get_window_draw_list();
-                            draw_list.add_rect(p0, p1, style[imgui::StyleColor::Text]).build();
-
-                            Some((text_color, background_color))
-                        } else {
-                            None
-                        }
-                    },
-                    None => None
-                };
-
-                ui.text(format!("{:2}.", i+1));
-                ui.same_line(0.0);
-                let wrap_pos = ui.push_text_wrap_pos(ui.content_region_avail()[0]);
-                ui.text(&story.title);
-
-
-                wrap_pos.pop(ui);
-                ui.same_line(0.0);
-                if let Some(color_stack) = color_stack {
-                    color_stack.1.pop(ui);
-                    color_stack.0.pop(ui);
+                None => {
+                    hn_state.items_to_fetch.queue_item(story_id);
+                    ui.text_disabled(format!("{:2}. ...", i + 1));
+                    return;
                 }
+            };
 
-                ui.text_disabled(format!(" ({})", &story.domain));
-                if state.view_mode != StoryListViewMode::Micro {
-                    let since = timeago::Formatter::new().convert_chrono(story.time, Utc::now());
-                    ui.text_disabled(format!("    {} points by {} {} | {} comments", story.score, &story.by, &since, story.descendants))
-                }
+            // Items are one row each, so long titles are cut to fit rather
+            // than wrapped.
+            let number = format!("{:2}. ", i + 1);
+            let domain = format!(" ({})", &story.domain);
+            let title = truncate(&story.title, width.saturating_sub(number.len() + spacing + domain.len()));
+            let line = format!("{}{}", number, title);
+            let domain_width = width.saturating_sub(line.chars().count() + spacing);
+            ui.text(&line);
+            ui.same_line(0.0);
+            ui.text_disabled(truncate(&domain, domain_width));
+            if state.view_mode != StoryListViewMode::Micro {
+                let since = timeago::Formatter::new().convert_chrono(story.time, Utc::now());
+                let line = format!("    {} points by {} {} | {} comments", story.score, &story.by, &since, story.descendants);
+                ui.text_disabled(truncate(&line, width))
+            }
+        });
+        self.open_activated(&list_events, story_ids);
+    }
 
-                let screen_pos = ui.cursor_screen_pos();
-                if screen_pos[1] + 3.0 > size.1 {
-                    self.max_stories.set((i + 1) as u32);
-                    break;
-                } else {
-                    if i == (self.max_stories.get() - 1) as usize && screen_pos[1] + 2.0 < size.1 {
-                        self.max_stories.set(self.max_stories.get() + 1);
-                    }
-                }
+    fn render_single_story(&self, draw_context: &DrawContext) {
+        let ui = &draw_context.ui;
+        let hn_state = self.hn_state.borrow();
+        let items = hn_state.items.borrow();
+        let story_id = match self.selected_story_id.get() {
+            Some(story_id) => story_id,
+            None => return,
+        };
+        let story = match items.get(&story_id) {
+            Some(HnItem::Story(story)) => story,
+            Some(_) => {
+                ui.text_disabled("Not a story. Esc: back");
+                return;
+            }
+            None => {
+                hn_state.items_to_fetch.queue_item(story_id);
+                ui.text_disabled("...");
+                return;
             }
+        };
+
+        let width = ui.content_region_avail()[0].max(0.0) as usize;
+        ui.text_wrapped(&imgui::ImString::new(&story.title));
+        if !story.url.is_empty() {
+            ui.text_disabled(truncate(&story.url, width));
+        }
+        let since = timeago::Formatter::new().convert_chrono(story.time, Utc::now());
+        let line = format!("{} points by {} {} | {} comments", story.score, &story.by, &since, story.descendants);
+        ui.text_disabled(truncate(&line, width));
+        if !story.text.is_empty() {
+            ui.text("");
+            ui.text_wrapped(&imgui::ImString::new(plain_text(&story.text)));
         }
+        ui.text("");
+        ui.text_disabled("Esc: back to the list");
     }
+}
 
-    fn render_single_story(&self, draw_context: &DrawContext, window_token: &imgui::WindowToken, pos: &(f32, f32), size: &(f32, f32)) {
+/// `text` cut to `width` characters, ending in "..." if it was longer.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    if width <= 3 {
+        return text.chars().take(width).collect();
     }
+    let mut cut: String = text.chars().take(width - 3).collect();
+    cut.push_str("...");
+    cut
+}
+
+/// The HTML of an item's text as plain text: paragraphs on their own lines,
+/// without tags, and with the entities the API uses decoded.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..].find('>').map_or(rest.len(), |end| start + end + 1);
+        if rest[start..end].starts_with("<p") {
+            text.push_str("\n\n");
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
 
 struct DrawContext<'a, 'b> {
@@ -578,7 +655,7 @@ impl fmt::Display for StoryListViewMode {
     }
 }
 
-const STATUS_WINDOW_HEIGHT: f32 = 4.0;
+const STATUS_WINDOW_HEIGHT: u16 = 4;
 
 struct UpdateStatus {
     update_in_progress: bool,
@@ -618,35 +695,88 @@ struct AppState {
     hn_state: Rc<RefCell<HnState>>,
     list_update_status: Rc<RefCell<UpdateStatus>>,
     show_status_window: bool,
-    active_window: Option<usize>,
+    focus: FocusRing,
     view_mode: StoryListViewMode,
+    panes: SplitPanes,
 }
 
 impl AppState {
-    fn new() -> AppState {
+    fn new(panes: SplitPanes) -> AppState {
         let hn_state = Rc::new(RefCell::new(HnState::new()));
         AppState {
-            windows: vec![
-                WindowData::new(WindowContent::Top, &hn_state),
-                WindowData::new(WindowContent::Top, &hn_state),
-                WindowData::new(WindowContent::Top, &hn_state),
-            ],
+            windows: (0..panes.len()).map(|_| WindowData::new(WindowContent::Top, &hn_state)).collect(),
             hn_state: hn_state,
             list_update_status: Rc::new(RefCell::new(UpdateStatus::new())),
             show_status_window: true,
-            active_window: Some(0),
+            focus: {
+                let mut focus = FocusRing::new().with_digit_jumps('0');
+                focus.focus(panes.focused());
+                focus
+            },
             view_mode: StoryListViewMode::Normal,
+            panes,
         }
     }
 
+    fn default_panes() -> SplitPanes {
+        AppState::configure_panes(SplitPanes::new(Direction::Horizontal, 3))
+    }
+
+    /// The limits aren't saved with the layout.
+    fn configure_panes(mut panes: SplitPanes) -> SplitPanes {
+        panes.max_panes = 3;
+        panes.min_size = 12;
+        panes
+    }
+
     fn process_input(&mut self, ui: &imgui::Ui) -> bool {
-        if ui.is_key_pressed('+' as u32) && self.windows.len() < 3 {
-            self.windows.push(WindowData::new(WindowContent::Top, &self.hn_state))
+        // Alt++ is handled with the other pane keys.
+        if ui.is_key_pressed('+' as u32) && !ui.io().key_alt {
+            if let Some(index) = self.panes.add() {
+                self.apply_pane_changes(&[PaneChange::Added(index), PaneChange::Focused(index)]);
+            }
         }
 
         !ui.is_key_pressed('q' as u32)
     }
 
+    /// Moves the focus between the story windows for Tab, Shift+Tab and the
+    /// digit keys, and tells each window whether it has it.
+    fn update_focus(&mut self, events: &[imtui::Event]) {
+        let entries = self
+            .windows
+            .iter()
+            .map(|window| FocusEntry {
+                name: imgui::ImString::new(&window.title),
+                rect: None,
+            })
+            .collect();
+        self.focus.set_entries(entries);
+        if self.focus.handle_events(events) {
+            self.panes.focus(self.focus.focused());
+        }
+        for (i, window) in self.windows.iter_mut().enumerate() {
+            window.set_active(self.focus.is_active(i));
+        }
+    }
+
+    /// Keeps the story windows in the same order as the panes.
+    fn apply_pane_changes(&mut self, changes: &[PaneChange]) {
+        for change in changes {
+            match *change {
+                PaneChange::Added(index) => {
+                    self.windows.insert(index, WindowData::new(WindowContent::Top, &self.hn_state));
+                }
+                PaneChange::Closed(index) => {
+                    self.windows.remove(index);
+                }
+                PaneChange::Swapped(a, b) => self.windows.swap(a, b),
+                PaneChange::Focused(index) => self.focus.focus(index),
+                PaneChange::Resized => {}
+            }
+        }
+    }
+
     fn update(&mut self) {
         // Update the list of items to be shown every 30 seconds
         if self.list_update_status.borrow().update_needed() {
@@ -683,29 +813,13 @@ impl AppState {
             tokio::task::spawn_local(fetch_and_assign);
         }
 
-        let active_window = self.active_window;
-        for (i, window) in self.windows.iter_mut().enumerate() {
-            let active = match active_window {
-                Some(index) => i == index,
-                _ => false,
-            };
-            window.set_active(active);
-        }
-
-        // Update selected story id
+        // Select the first story of each list once there are stories
         if let Some(last_list_refresh) = &self.hn_state.borrow().last_list_refresh {
             for window in self.windows.iter_mut() {
-                let story_ids = match window.window_content {
-                    WindowContent::Top => &last_list_refresh.top_ids,
-                    WindowContent::Show => &last_list_refresh.show_ids,
-                    WindowContent::Ask => &last_list_refresh.ask_ids,
-                    WindowContent::New => &last_list_refresh.new_ids,
-                };
-
-                // TODO: detect when selected story id is no longer in the data
-                // TODO: detect when selected story id would no longer be visible on screen
-                if window.hovered_story_id.is_none() {
-                    window.hovered_story_id = Some(story_ids[0]);
+                let has_stories = !window.story_ids(last_list_refresh).is_empty();
+                let list = window.list.get_mut();
+                if has_stories && list.selected().is_none() {
+                    list.select(Some(0));
                 }
             }
         }
@@ -716,14 +830,60 @@ struct HntermApp {
     imgui: imgui::Context,
     imtui: imtui::Ncurses,
     state: AppState,
+    logs: Option<imtui::log::LogBuffer>,
+    log_console: imtui::log::LogConsole,
+    /// The bundled theme in use, if any.
+    theme_index: Option<usize>,
+    theme_watcher: Option<imtui::theme::ThemeWatcher>,
+    settings: Option<imtui::Settings>,
 }
 
 impl HntermApp {
-    fn new(imgui: imgui::Context, imtui: imtui::Ncurses) -> HntermApp {
+    fn new(imgui: imgui::Context, imtui: imtui::Ncurses, logs: Option<imtui::log::LogBuffer>,
+           theme_index: Option<usize>, theme_watcher: Option<imtui::theme::ThemeWatcher>,
+           settings: Option<imtui::Settings>) -> HntermApp {
+        let panes = settings
+            .as_ref()
+            .and_then(|settings| settings.get("panes"))
+            .and_then(|json| serde_json::from_str::<SplitPanes>(json).ok())
+            .filter(|panes| panes.len() <= 3)
+            .map(AppState::configure_panes)
+            .unwrap_or_else(AppState::default_panes);
         HntermApp {
             imgui,
             imtui,
-            state: AppState::new(),
+            state: AppState::new(panes),
+            logs,
+            log_console: imtui::log::LogConsole::new(),
+            theme_index,
+            theme_watcher,
+            settings,
+        }
+    }
+
+    fn update_theme(&mut self) {
+        let theme_cycled = self.imtui.events().iter().any(|event| match event {
+            imtui::Event::Key(key) => key.key == imtui::Key::F(10),
+            _ => false,
+        });
+        if theme_cycled {
+            // A bundled theme replaces a watched file until restart.
+            self.theme_watcher = None;
+            let index = self.theme_index.map_or(0, |i| (i + 1) % imtui::theme::BUNDLED.len());
+            self.theme_index = Some(index);
+            if let Some(theme) = imtui::theme::Theme::bundled(imtui::theme::BUNDLED[index]) {
+                theme.apply(self.imgui.style_mut());
+            }
+            if let Some(settings) = &mut self.settings {
+                settings.set("theme", imtui::theme::BUNDLED[index]);
+            }
+        }
+        if let Some(watcher) = &mut self.theme_watcher {
+            match watcher.poll() {
+                Some(Ok(theme)) => theme.apply(self.imgui.style_mut()),
+                Some(Err(error)) => log::warn!(target: "theme", "{}: {}", watcher.path().display(), error),
+                None => {}
+            }
         }
     }
 
@@ -738,10 +898,35 @@ impl HntermApp {
             );
         }
 
-        self.imtui.set_active();
         self.imtui.new_frame();
+        // Keys typed into a text field aren't commands. The focus ring sorts
+        // that out by itself, so clicks still move the focus while typing.
+        let typing = self.imgui.io().want_text_input;
+        let commands: &[imtui::Event] = if typing { &[] } else { self.imtui.events() };
+        let pane_changes = self.state.panes.handle_events(commands);
+        self.state.apply_pane_changes(&pane_changes);
+        self.state.update_focus(self.imtui.events());
+        if let Some(window) = self.state.windows.get(self.state.focus.focused()) {
+            window.handle_events(commands);
+        }
+        let screenshot_requested = self.imtui.events().iter().any(|event| match event {
+            imtui::Event::Key(key) => key.key == imtui::Key::F(12),
+            _ => false,
+        });
+        if screenshot_requested {
+            let _ = self.imtui.screenshot().save("hnterm.svg");
+        }
+        let overlay_toggled = self.imtui.events().iter().any(|event| match event {
+            imtui::Event::Key(key) => key.key == imtui::Key::F(11),
+            _ => false,
+        });
+        if overlay_toggled {
+            self.imtui.debug_overlay_mut().toggle();
+        }
+        self.update_theme();
 
         let mut ui = self.imgui.frame();
+        self.state.focus.apply(&ui);
         if !self.state.process_input(&ui) {
             return false;
         }
@@ -751,42 +936,48 @@ impl HntermApp {
             ui: &mut ui,
         };
 
-        HntermApp::render(&self.state, &draw_context);
+        HntermApp::render(&mut self.state, &draw_context);
+        if let Some(logs) = &self.logs {
+            self.log_console.window(&ui, &imgui::ImString::new("Log"), logs);
+        }
         let draw_data = ui.render();
         self.imtui.render(draw_data);
+        if let Some(settings) = &mut self.settings {
+            if let Ok(panes) = serde_json::to_string(&self.state.panes) {
+                settings.set("panes", panes);
+            }
+            if let Err(error) = settings.autosave(&mut self.imgui) {
+                log::warn!(target: "settings", "{}: {}", settings.dir().display(), error);
+            }
+        }
         true
     }
 
-    fn render(state: &AppState, draw_context: &DrawContext) {
+    fn render(state: &mut AppState, draw_context: &DrawContext) {
         if state.windows.len() == 0 {
             return;
         }
 
-        let display_size = draw_context.ui.io().display_size;
+        let screen = Rect::screen(draw_context.ui.io().display_size);
+        let mut rows = vec![Constraint::Min(1)];
+        if state.show_status_window {
+            rows.push(Constraint::Fixed(STATUS_WINDOW_HEIGHT));
+        }
+        let rows = Layout::vertical(rows).split(screen);
+        let mut columns = state.panes.split(rows[0]);
+        let state: &AppState = state;
         {
-            let windows_to_draw = if display_size[0] < 50.0 {
+            let windows_to_draw = if screen.width < 50 {
+                columns = vec![rows[0]];
                 &state.windows.as_slice()[0..1]
             } else {
                 state.windows.as_slice()
             };
 
-            let window_width = display_size[0] / windows_to_draw.len() as f32;
-            let mut window_height = display_size[1];
-            if state.show_status_window {
-                window_height -= STATUS_WINDOW_HEIGHT;
-            }
-
-            let window_size = (window_width, window_height);
-
-            let mut window_pos = (0.0, 0.0);
-            let num_windows = windows_to_draw.len();
-            for (i, wd) in windows_to_draw.iter().enumerate() {
-                let mut actual_window_size = window_size;
-                if i != num_windows - 1 {
-                    actual_window_size.0 = (actual_window_size.0 - 1.1).floor();
-                }
-                wd.render(state, draw_context, &window_pos, &actual_window_size);
-                window_pos.0 = (window_pos.0 + window_width).ceil();
+            for (wd, column) in windows_to_draw.iter().zip(columns) {
+                let window_pos = (column.x as f32, column.y as f32);
+                let window_size = (column.width as f32, column.height as f32);
+                wd.render(state, draw_context, &window_pos, &window_size);
             }
         }
 
@@ -796,8 +987,8 @@ impl HntermApp {
                 format!("Status | Story List Mode: {}", state.view_mode.to_string())
             );
             let window = imgui::Window::new(&title)
-                .position([0., display_size[1] - STATUS_WINDOW_HEIGHT], imgui::Condition::Always)
-                .size([display_size[0], STATUS_WINDOW_HEIGHT], imgui::Condition::Always)
+                .position(rows[1].position(), imgui::Condition::Always)
+                .size(rows[1].size(), imgui::Condition::Always)
                 .flags(imgui::WindowFlags::NO_COLLAPSE |
                        imgui::WindowFlags::NO_RESIZE |
                        imgui::WindowFlags::NO_MOVE |
@@ -836,41 +1027,20 @@ impl HntermApp {
     }
 }
 
-fn set_color_scheme(context: &mut imgui::Context, dark: bool) {
-    let light_colors = [
-        (imgui::StyleColor::Text, [0.0, 0.0, 0.0, 1.0]),
-        (imgui::StyleColor::TextDisabled, [0.6, 0.6, 0.6, 1.0]),
-        (imgui::StyleColor::WindowBg, [0.96, 0.96, 0.94, 1.0]),
-        (imgui::StyleColor::TitleBg, [1.0, 0.4, 0.0, 1.0]),
-        (imgui::StyleColor::TitleBgActive, [1.0, 0.4, 0.0, 1.0]),
-        (imgui::StyleColor::TitleBgCollapsed, [0.69, 0.25, 0.0, 1.0]),
-        (imgui::StyleColor::ChildBg, [0.96, 0.96, 0.94, 1.0]),
-        (imgui::StyleColor::PopupBg, [0.96, 0.96, 0.94, 1.0]),
-        (imgui::StyleColor::ModalWindowDimBg, [0.0, 0.0, 0.0, 0.0])
-    ];
-    let dark_colors = [
-        (imgui::StyleColor::Text, [0.0, 1.0, 0.0, 1.0]),
-        (imgui::StyleColor::TextDisabled, [0.6, 0.6, 0.6, 1.0]),
-        (imgui::StyleColor::WindowBg, [0.0, 0.0, 0.0, 1.0]),
-        (imgui::StyleColor::TitleBg, [0.1, 0.2, 0.1, 1.0]),
-        (imgui::StyleColor::TitleBgActive, [0.1, 0.2, 0.1, 1.0]),
-        (imgui::StyleColor::TitleBgCollapsed, [0.5, 1.0, 0.5, 1.0]),
-        (imgui::StyleColor::ChildBg, [0.0, 0.0, 0.0, 1.0]),
-        (imgui::StyleColor::PopupBg, [0.0, 0.1, 0.0, 1.0]),
-        (imgui::StyleColor::ModalWindowDimBg, [0.0, 0.0, 0.0, 0.0])
-    ];
-    let colors = if dark { dark_colors } else { light_colors };
-    for (style_color, values) in colors.iter() {
-        context.style_mut()[*style_color] = *values;
-    }
-}
-
 #[derive(Clap)]
 struct Opts {
     #[clap(short, long, about = "Wait for debugger at startup")]
     debug: bool,
-    #[clap(short, long, about = "Verbose logging to stderr")]
+    #[clap(short, long, about = "Verbose logging to a console window")]
     verbose: bool,
+    #[clap(short, long, about = "Record the session to an asciicast file")]
+    record: Option<String>,
+    #[clap(short, long, about = "Capture input to a file, for replaying with --replay")]
+    capture: Option<String>,
+    #[clap(long, about = "Replay input captured with --capture")]
+    replay: Option<String>,
+    #[clap(short, long, about = "A bundled theme, or a .toml or .json theme file to reload when it changes")]
+    theme: Option<String>,
 }
 
 #[tokio::main]
@@ -880,25 +1050,63 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
         debug_here!();
     }
 
-    if opts.verbose {
-        let mut log_builder = env_logger::Builder::new();
-        log_builder.target(env_logger::Target::Stderr)
+    let logs = if opts.verbose {
+        let logs = imtui::log::LogBuffer::new(1000)
+            .filter_level(log::LevelFilter::Off)
             .filter_module("fetch_queue", log::LevelFilter::Trace)
+            .filter_module("theme", log::LevelFilter::Warn)
+            .filter_module("settings", log::LevelFilter::Warn)
             .filter_module("reqwest::connect", log::LevelFilter::Trace)
             .filter_module("reqwest::async_impl::client", log::LevelFilter::Trace)
-            .init();
-    }
-
-    let mut imgui = imgui::Context::create();
-    imgui.set_ini_filename(None);
-    let imtui = imtui::Ncurses::init(true, 60.0, -1.0);
+            .install()?;
+        Some(logs)
+    } else {
+        None
+    };
 
-    set_color_scheme(&mut imgui, false);
+    let mut imgui = imtui::context().preset(imtui::StylePreset::Paper).build();
+    let settings = imtui::Settings::open("hnterm").ok();
+    if let Some(settings) = &settings {
+        settings.load_imgui(&mut imgui);
+    }
+    let theme = opts.theme.clone().or_else(|| settings.as_ref().and_then(|s| s.get("theme")).map(String::from));
+    let mut theme_index = None;
+    let mut theme_watcher = None;
+    if let Some(theme) = &theme {
+        match imtui::theme::Theme::bundled(theme) {
+            Some(bundled) => {
+                bundled.apply(imgui.style_mut());
+                theme_index = imtui::theme::BUNDLED.iter().position(|name| name == theme);
+            }
+            // Anything else has to be a theme file that loads.
+            None => match imtui::theme::Theme::load(theme) {
+                Ok(loaded) => {
+                    loaded.apply(imgui.style_mut());
+                    theme_watcher = Some(imtui::theme::ThemeWatcher::new(theme));
+                }
+                Err(error) if opts.theme.is_some() => {
+                    let bundled = imtui::theme::BUNDLED.join(", ");
+                    return Err(format!("--theme {}: {} (bundled themes: {})", theme, error, bundled).into());
+                }
+                Err(error) => log::warn!(target: "theme", "{}: {}", theme, error),
+            },
+        }
+    }
+    let mut imtui = imtui::Ncurses::init(true, 60.0, 5.0)?;
+    if let Some(path) = &opts.record {
+        imtui.set_recorder(Some(imtui::Recorder::create(path)?));
+    }
+    if let Some(path) = &opts.capture {
+        imtui.set_input_capture(Some(imtui::InputCapture::create(path)?));
+    }
+    if let Some(path) = &opts.replay {
+        imtui.set_input_replay(Some(imtui::InputReplay::open(path)?));
+    }
 
     let local_set = tokio::task::LocalSet::new();
 
     local_set.run_until(async move {
-        let mut app = HntermApp::new(imgui, imtui);
+        let mut app = HntermApp::new(imgui, imtui, logs, theme_index, theme_watcher, settings);
 
         let future_fn = |cx: &mut Context| {
             if app.process_frame() {
@@ -910,7 +1118,21 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
         };
 
         let future = poll_fn(future_fn);
-        future.await
+        future.await;
+
+        if let Some(settings) = &mut app.settings {
+            if let Ok(panes) = serde_json::to_string(&app.state.panes) {
+                settings.set("panes", panes);
+            }
+            let _ = settings.save(&mut app.imgui);
+        }
+
+        if let Some(recorder) = app.imtui.set_recorder(None) {
+            let _ = recorder.finish();
+        }
+        if let Some(capture) = app.imtui.set_input_capture(None) {
+            let _ = capture.finish();
+        }
     }).await;
     Ok(())
 }
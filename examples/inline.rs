@@ -0,0 +1,77 @@
+use imtui;
+use imtui::Backend;
+use imgui;
+use libc;
+use std::io::{self, BufRead};
+
+/// Lets the user pick one of the lines piped into stdin, or one of a few
+/// fruits, and prints it to stdout: `ls | cargo run --example inline`.
+fn main() {
+    let mut items: Vec<String> = ["apple", "banana", "cherry", "durian", "elderberry"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        items = io::stdin().lock().lines().filter_map(|line| line.ok()).collect();
+    }
+    if items.is_empty() {
+        return;
+    }
+
+    let mut imgui = imtui::context().build();
+
+    let options = imtui::TerminalOptions {
+        tty: imtui::TtySource::ControllingTerminal,
+        viewport: imtui::Viewport::Inline(items.len().min(10) as u16 + 2),
+        keep_final_frame: false,
+        ..imtui::TerminalOptions::default()
+    };
+    let mut imtui = imtui::Terminal::init(options).expect("no controlling terminal");
+
+    let mut selected = 0;
+    let mut chosen = None;
+    loop {
+        imtui.new_frame();
+        for event in imtui.events() {
+            if let imtui::Event::Key(key) = event {
+                match key.key {
+                    imtui::Key::Up | imtui::Key::Char('k') => selected = (selected + items.len() - 1) % items.len(),
+                    imtui::Key::Down | imtui::Key::Char('j') => selected = (selected + 1) % items.len(),
+                    imtui::Key::Enter => chosen = Some(items[selected].clone()),
+                    _ => {}
+                }
+            }
+        }
+        if chosen.is_some() || imtui.events().iter().any(|e| match e {
+            imtui::Event::Key(key) => key.key == imtui::Key::Escape,
+            _ => false,
+        }) {
+            break;
+        }
+
+        let ui = imgui.frame();
+        let (width, height) = imtui.viewport_size();
+        let title = imgui::ImString::new("Pick one");
+        let window = imgui::Window::new(&title)
+            .position([0.0, 0.0], imgui::Condition::Always)
+            .size([width as f32, height as f32], imgui::Condition::Always)
+            .flags(imgui::WindowFlags::NO_COLLAPSE |
+                   imgui::WindowFlags::NO_RESIZE |
+                   imgui::WindowFlags::NO_MOVE);
+        if let Some(window_token) = window.begin(&ui) {
+            for (i, item) in items.iter().enumerate() {
+                let marker = if i == selected { ">" } else { " " };
+                ui.text(format!("{} {}", marker, item));
+            }
+            window_token.end(&ui);
+        }
+
+        let draw_data = ui.render();
+        imtui.render(draw_data);
+    }
+
+    drop(imtui);
+    if let Some(chosen) = chosen {
+        println!("{}", chosen);
+    }
+}
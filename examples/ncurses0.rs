@@ -1,13 +1,13 @@
 use imtui;
+use imtui::Backend;
 use imgui;
 use std::time::SystemTime;
 use std;
 
 fn main() {
-    let mut imgui = imgui::Context::create();
-    imgui.set_ini_filename(None);
+    let mut imgui = imtui::context().build();
 
-    let mut imtui = imtui::Ncurses::init(true, 60.0, -1.0);
+    let mut imtui = imtui::Ncurses::init(true, 60.0, -1.0).expect("failed to initialize ncurses");
     let now = SystemTime::now();
     let mut nframes = 0 as i32;
     let mut fval = 123.0;
@@ -21,13 +21,16 @@ fn main() {
         let title = imgui::ImString::new("Hello, world!");
         let window = imgui::Window::new(&title)
             .position([0.0, 0.0], imgui::Condition::FirstUseEver)
-            .size([50.0, 10.0], imgui::Condition::FirstUseEver);
+            .size([60.0, 12.0], imgui::Condition::FirstUseEver);
         if let Some(windowToken) = window.begin(&ui) {
             ui.text(format!("NFrames = {}", nframes));
 
             let imgui_io = ui.io();
             ui.text(format!("Mouse Post: x = {}, y = {}", imgui_io.mouse_pos[0], imgui_io.mouse_pos[1]));
             ui.text(format!("Time per frame {0:.3} ms/frame ({1:.1} FPS)", 1000.0 / imgui_io.framerate, imgui_io.framerate));
+            let stats = imtui.frame_stats().average();
+            ui.text(format!("Rasterize {0:.3} ms, flush {1:.3} ms, {2} cells changed",
+                            stats.rasterize.as_secs_f64() * 1000.0, stats.flush.as_secs_f64() * 1000.0, stats.changed_cells));
 
             ui.text("Float:");
             ui.same_line(0.0);
@@ -0,0 +1,59 @@
+use imtui;
+use imgui;
+use std::env;
+use std::time::Instant;
+
+/// A counter that keeps running while nobody watches.
+///
+/// Start it with `cargo run --example session -- serve`, then attach from
+/// other terminals with `cargo run --example session -- attach`, adding
+/// `--read-only` to only watch. Ctrl-\ detaches.
+fn main() {
+    let path = env::temp_dir().join("imtui-session.sock");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("serve") => serve(&path),
+        Some("attach") => {
+            let mut options = imtui::session::AttachOptions::default();
+            if args.iter().any(|a| a == "--read-only") {
+                options.access = imtui::session::Access::ReadOnly;
+            }
+            imtui::session::attach(&path, &options).expect("failed to attach");
+        }
+        _ => eprintln!("usage: session serve | session attach [--read-only]"),
+    }
+}
+
+fn serve(path: &std::path::Path) {
+    let mut imgui = imtui::context().build();
+    let mut server = imtui::session::Server::bind(path, true, 60.0, 2.0).expect("failed to listen");
+    let started = Instant::now();
+    let mut clicks = 0;
+    let mut quit = false;
+
+    while !quit {
+        server.new_frame();
+        let viewers = server.viewers();
+
+        let ui = imgui.frame();
+        let title = imgui::ImString::new("Session");
+        let window = imgui::Window::new(&title)
+            .position([2.0, 1.0], imgui::Condition::Once)
+            .size([44.0, 10.0], imgui::Condition::Once);
+        if let Some(window_token) = window.begin(&ui) {
+            ui.text(format!("Up for {} seconds", started.elapsed().as_secs()));
+            ui.text(format!("{} viewer(s) attached", viewers.len()));
+            if ui.button(&imgui::ImString::new("Click me"), [0.0, 0.0]) {
+                clicks += 1;
+            }
+            ui.text(format!("Clicked {} times", clicks));
+            if ui.button(&imgui::ImString::new("Stop the server"), [0.0, 0.0]) {
+                quit = true;
+            }
+            window_token.end(&ui);
+        }
+
+        let draw_data = ui.render();
+        server.render(draw_data);
+    }
+}
@@ -0,0 +1,58 @@
+use imtui;
+use imtui::Backend;
+use imgui;
+use std::net::{Shutdown, TcpListener};
+
+/// Serves a small UI to one TCP client at a time. Connect from a terminal
+/// with `socat -,raw,echo=0 tcp:localhost:7777`.
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:7777").expect("failed to listen on port 7777");
+    for client in listener.incoming() {
+        let client = match client {
+            Ok(client) => client,
+            Err(_) => continue,
+        };
+        let reader = client.try_clone().expect("failed to clone the socket");
+        let socket = client.try_clone().expect("failed to clone the socket");
+
+        let mut imgui = imtui::context().build();
+        let mut imtui = match imtui::Stream::new(reader, client, (80, 24), true, 60.0, 5.0) {
+            Ok(imtui) => imtui,
+            Err(_) => continue,
+        };
+        imtui.set_shutdown(move || {
+            let _ = socket.shutdown(Shutdown::Both);
+        });
+
+        let mut clicks = 0;
+        while !imtui.is_closed() {
+            imtui.new_frame();
+            let quit = imtui.events().iter().any(|e| match e {
+                imtui::Event::Key(key) => key.key == imtui::Key::Char('q'),
+                _ => false,
+            });
+            if quit {
+                break;
+            }
+
+            let ui = imgui.frame();
+            let title = imgui::ImString::new("imtui over a socket");
+            let window = imgui::Window::new(&title)
+                .position([2.0, 1.0], imgui::Condition::Once)
+                .size([40.0, 8.0], imgui::Condition::Once);
+            if let Some(window_token) = window.begin(&ui) {
+                ui.text("Press q to disconnect.");
+                if ui.button(&imgui::ImString::new("Click me"), [0.0, 0.0]) {
+                    clicks += 1;
+                }
+                ui.text(format!("Clicked {} times", clicks));
+                window_token.end(&ui);
+            }
+
+            let draw_data = ui.render();
+            if imtui.render(draw_data).is_err() {
+                break;
+            }
+        }
+    }
+}
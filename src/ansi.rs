@@ -0,0 +1,179 @@
+//! Driving a terminal with plain ANSI escape sequences, for the backends that
+//! don't go through ncurses.
+
+use crate::color::ResolvedColors;
+use crate::cursor::CursorShape;
+use crate::frontend::Frontend;
+use crate::screen::{self, Cell};
+use crate::sys;
+use imgui;
+use std::io::Write;
+
+/// Returns the sequence that turns on the terminal modes the backends use.
+pub(crate) fn enable_modes(mouse_support: bool) -> Vec<u8> {
+    let mut out = b"\x1b[?25l\x1b[?2004h\x1b[?1004h".to_vec();
+    if mouse_support {
+        out.extend_from_slice(b"\x1b[?1003h\x1b[?1006h");
+    }
+    out
+}
+
+/// Returns the sequence that undoes `enable_modes` and any styling.
+pub(crate) fn disable_modes(mouse_support: bool) -> Vec<u8> {
+    let mut out = b"\x1b[0m\x1b[0 q\x1b[?2004l\x1b[?1004l".to_vec();
+    if mouse_support {
+        out.extend_from_slice(b"\x1b[?1006l\x1b[?1003l");
+    }
+    out.extend_from_slice(b"\x1b[?25h");
+    out
+}
+
+/// One terminal's copy of the rendered screen: what was last drawn on it,
+/// and the state of its cursor.
+pub(crate) struct AnsiOutput {
+    painter: AnsiPainter,
+    cursor_visible: bool,
+    applied_cursor_shape: Option<CursorShape>,
+}
+
+impl AnsiOutput {
+    pub fn new() -> AnsiOutput {
+        AnsiOutput {
+            painter: AnsiPainter::new(),
+            cursor_visible: false,
+            applied_cursor_shape: None,
+        }
+    }
+
+    /// Forces the next draw to repaint everything.
+    pub fn invalidate(&mut self) {
+        self.painter.invalidate();
+    }
+
+    /// Appends the output that brings the terminal up to date with `screen`
+    /// to `out`, leaving the cursor on `caret`, from `Frontend::caret`.
+    /// Returns the number of cells that changed.
+    pub fn draw(&mut self, screen: &sys::ImTui::TScreen, origin: u16, colors: &ResolvedColors,
+                caret: Option<(i32, i32)>, cursor_shape: CursorShape, out: &mut Vec<u8>) -> usize {
+        let changed = self.painter.draw(screen, origin, colors, out);
+        match caret {
+            Some((x, y)) if x >= 0 && y >= 0 && x < screen.nx && y < screen.ny => {
+                let _ = write!(out, "\x1b[{};{}H", origin as i32 + y + 1, x + 1);
+                if !self.cursor_visible {
+                    out.extend_from_slice(b"\x1b[?25h");
+                    self.cursor_visible = true;
+                }
+            }
+            _ => {
+                if self.cursor_visible {
+                    out.extend_from_slice(b"\x1b[?25l");
+                    self.cursor_visible = false;
+                }
+            }
+        }
+        if self.applied_cursor_shape != Some(cursor_shape) {
+            let _ = write!(out, "\x1b[{} q", cursor_shape.decscusr());
+            self.applied_cursor_shape = Some(cursor_shape);
+        }
+        changed
+    }
+}
+
+/// Rasterizes a frame with `frontend`, then appends the output that brings
+/// the terminal drawn by `output` up to date to `out`, and counts it in the
+/// frame's stats. The caller ends the frame with `stats.end_frame()` once
+/// the output is written.
+pub(crate) fn render<F: FnMut() -> bool>(frontend: &mut Frontend, output: &mut AnsiOutput, draw_data: &imgui::DrawData,
+                                         origin: u16, input_pending: F, out: &mut Vec<u8>) {
+    frontend.rasterize(draw_data, input_pending);
+    let colors = frontend.colors();
+    let before = out.len();
+    let caret = frontend.caret();
+    frontend.stats.current.changed_cells = output.draw(frontend.screen(), origin, &colors, caret,
+                                                       frontend.cursor_shape, out);
+    frontend.stats.current.output_bytes = out.len() - before;
+}
+
+/// Draws a rasterized screen as escape sequences, only touching rows that
+/// changed since the previous frame.
+pub(crate) struct AnsiPainter {
+    prev: Vec<sys::ImTui::TCell>,
+    nx: i32,
+    ny: i32,
+    colors: ResolvedColors,
+}
+
+impl AnsiPainter {
+    pub fn new() -> AnsiPainter {
+        AnsiPainter {
+            prev: vec![],
+            nx: 0,
+            ny: 0,
+            colors: ResolvedColors::default(),
+        }
+    }
+
+    /// Forces the next draw to repaint every row.
+    pub fn invalidate(&mut self) {
+        self.nx = 0;
+        self.ny = 0;
+    }
+
+    /// Appends the output for `screen` to `out`, with the top row of the
+    /// screen drawn on terminal row `origin`, counting from 0. Returns the
+    /// number of cells that changed.
+    pub fn draw(&mut self, screen: &sys::ImTui::TScreen, origin: u16, colors: &ResolvedColors, out: &mut Vec<u8>) -> usize {
+        let cells = screen::cells(screen);
+        let (nx, ny) = (screen.nx, screen.ny);
+        let compare = nx == self.nx && ny == self.ny && *colors == self.colors;
+        if !compare {
+            self.prev.clear();
+            self.prev.resize(cells.len(), 0);
+            self.nx = nx;
+            self.ny = ny;
+            self.colors = *colors;
+        }
+
+        let mut changed = 0;
+        for y in 0..ny as usize {
+            let range = y * nx as usize..(y + 1) * nx as usize;
+            if compare && cells[range.clone()] == self.prev[range.clone()] {
+                continue;
+            }
+            changed += if compare {
+                cells[range.clone()].iter().zip(&self.prev[range.clone()]).filter(|(a, b)| a != b).count()
+            } else {
+                range.len()
+            };
+
+            let _ = write!(out, "\x1b[{};1H", origin as usize + y + 1);
+            let mut last = None;
+            for &raw in &cells[range.clone()] {
+                let cell = Cell::from(raw);
+                let (fg, bg) = colors.apply(cell);
+                if last != Some((fg, bg)) {
+                    write_sgr(out, fg, bg);
+                    last = Some((fg, bg));
+                }
+                let mut utf8 = [0u8; 4];
+                out.extend_from_slice(cell.to_char().encode_utf8(&mut utf8).as_bytes());
+            }
+            out.extend_from_slice(b"\x1b[0m");
+            self.prev[range.clone()].copy_from_slice(&cells[range]);
+        }
+        changed
+    }
+}
+
+/// Selects a foreground and background palette color, with `None` standing
+/// for the terminal default.
+pub(crate) fn write_sgr(out: &mut Vec<u8>, fg: Option<u8>, bg: Option<u8>) {
+    let _ = match fg {
+        Some(fg) => write!(out, "\x1b[38;5;{}m", fg),
+        None => write!(out, "\x1b[39m"),
+    };
+    let _ = match bg {
+        Some(bg) => write!(out, "\x1b[48;5;{}m", bg),
+        None => write!(out, "\x1b[49m"),
+    };
+}
@@ -0,0 +1,141 @@
+use crate::clipboard::ClipboardProvider;
+use crate::color::DefaultColors;
+use crate::cursor::CursorShape;
+use crate::export::Screenshot;
+use crate::frontend::Frontend;
+use crate::input::Event;
+use crate::overlay::DebugOverlay;
+use crate::record::Recorder;
+use crate::replay::{InputCapture, InputReplay};
+use crate::stats::FrameHistory;
+use std::time::Duration;
+
+/// The settings and tools every backend has: frame pacing, colors, the
+/// cursor, recording and replaying input, and frame stats.
+///
+/// `Ncurses`, `Terminal`, `Stream`, `session::Server` and `Headless` all
+/// implement it; bring it into scope with `use imtui::Backend;`. Each
+/// backend has its own `new_frame` and `render`.
+pub trait Backend {
+    #[doc(hidden)]
+    fn frontend(&self) -> &Frontend;
+
+    #[doc(hidden)]
+    fn frontend_mut(&mut self) -> &mut Frontend;
+
+    /// Keeps drawing at the active frame rate, e.g. while an animation runs,
+    /// until `set_inactive` is called. Without it, the active rate is used
+    /// while there has been input within the idle timeout.
+    fn set_active(&mut self) {
+        self.frontend_mut().activity.forced = true;
+    }
+
+    /// Undoes `set_active`.
+    fn set_inactive(&mut self) {
+        self.frontend_mut().activity.forced = false;
+    }
+
+    /// Sets how long after the last input frames are drawn at the active
+    /// rate before dropping to the idle rate.
+    fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.frontend_mut().activity.idle_timeout = timeout;
+    }
+
+    /// True unless the terminal reported that its window lost focus. While
+    /// unfocused, frames are always drawn at the idle rate.
+    fn is_focused(&self) -> bool {
+        self.frontend().activity.focused
+    }
+
+    /// Draws the given colors with the terminal's default foreground and
+    /// background, e.g. `DefaultColors::from_style(imgui.style())` to blend
+    /// windows into a transparent or themed terminal.
+    fn set_default_colors(&mut self, default_colors: DefaultColors) {
+        self.frontend_mut().set_default_colors(default_colors);
+    }
+
+    /// The colors set with `set_default_colors`.
+    fn default_colors(&self) -> DefaultColors {
+        self.frontend().default_colors()
+    }
+
+    /// Sets the shape of the terminal cursor, which is shown on the caret
+    /// while a text input is active.
+    fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.frontend_mut().cursor_shape = shape;
+    }
+
+    /// The shape set with `set_cursor_shape`.
+    fn cursor_shape(&self) -> CursorShape {
+        self.frontend().cursor_shape
+    }
+
+    /// Starts recording every frame to `recorder`, or stops with `None`.
+    /// Returns the recorder that was set before, to `finish` it.
+    fn set_recorder(&mut self, recorder: Option<Recorder>) -> Option<Recorder> {
+        self.frontend_mut().set_recorder(recorder)
+    }
+
+    /// Starts capturing the input and timing of every frame, or stops with
+    /// `None`. Returns the capture that was set before, to `finish` it.
+    fn set_input_capture(&mut self, capture: Option<InputCapture>) -> Option<InputCapture> {
+        self.frontend_mut().set_input_capture(capture)
+    }
+
+    /// Takes the input, display size and timing of the following frames from
+    /// `replay` instead of the terminal, or stops with `None`.
+    fn set_input_replay(&mut self, replay: Option<InputReplay>) -> Option<InputReplay> {
+        self.frontend_mut().set_input_replay(replay)
+    }
+
+    /// True while a replay has frames left.
+    fn is_replaying(&self) -> bool {
+        self.frontend().is_replaying()
+    }
+
+    /// Sets an external clipboard, such as a `CommandClipboard`, to use
+    /// before falling back to OSC 52 and the in-process buffer.
+    fn set_clipboard_provider(&mut self, provider: Option<Box<dyn ClipboardProvider>>) {
+        self.frontend_mut().set_clipboard_provider(provider);
+    }
+
+    /// Enables or disables copying to and pasting from the terminal's
+    /// clipboard with OSC 52. Copying is enabled by default. Pasting is
+    /// enabled by default only on `Ncurses` terminals that advertise OSC 52,
+    /// and only `Ncurses` and `Terminal` can paste from it at all.
+    fn set_osc52_clipboard(&mut self, write: bool, read: bool) {
+        self.frontend_mut().set_osc52_clipboard(write, read);
+    }
+
+    /// Copies the last rendered frame, e.g. to save it as HTML or SVG.
+    fn screenshot(&self) -> Screenshot {
+        self.frontend().screenshot()
+    }
+
+    /// Timings and counts of the last frames.
+    fn frame_stats(&self) -> &FrameHistory {
+        &self.frontend().stats.history
+    }
+
+    /// The frame history, e.g. to change how many frames it keeps.
+    fn frame_stats_mut(&mut self) -> &mut FrameHistory {
+        &mut self.frontend_mut().stats.history
+    }
+
+    /// The debug panel drawn over the app, hidden until its `visible` is
+    /// set.
+    fn debug_overlay(&self) -> &DebugOverlay {
+        &self.frontend().overlay
+    }
+
+    /// The debug panel, e.g. to `toggle` it from a key binding.
+    fn debug_overlay_mut(&mut self) -> &mut DebugOverlay {
+        &mut self.frontend_mut().overlay
+    }
+
+    /// The input events received by the last call to `new_frame`, including
+    /// focus changes.
+    fn events(&self) -> &[Event] {
+        self.frontend().events()
+    }
+}
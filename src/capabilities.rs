@@ -0,0 +1,279 @@
+use crate::curses;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::time::{Duration, Instant};
+
+/// Features of the terminal the backend is running in.
+///
+/// `Capabilities::detect` fills this in from terminfo and the environment,
+/// which is cheap but only as accurate as `TERM` and friends. Terminals that
+/// answer device queries can be probed with `Ncurses::query_capabilities` to
+/// refine the guesses.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    /// Value of `TERM`.
+    pub term: String,
+    /// Name and version reported by the terminal in reply to XTVERSION.
+    pub version: Option<String>,
+    /// Number of palette colors, from the terminfo `colors` capability.
+    pub colors: u32,
+    pub truecolor: bool,
+    pub mouse: bool,
+    pub unicode: bool,
+    pub synchronized_output: bool,
+    /// From the terminfo `BE` capability, or the terminal's answer to a
+    /// DECRQM query.
+    pub bracketed_paste: bool,
+    pub osc52: bool,
+}
+
+impl Capabilities {
+    /// Detects capabilities from terminfo and environment variables. Must be
+    /// called after ncurses has been initialized.
+    pub fn detect() -> Capabilities {
+        let term = env::var("TERM").unwrap_or_default();
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        Capabilities {
+            version: None,
+            colors: tigetnum("colors").unwrap_or(8),
+            truecolor: colorterm == "truecolor" || colorterm == "24bit" || tigetflag("RGB") || tigetflag("Tc"),
+            mouse: tigetstr("kmous") || unsafe { curses::has_mouse() },
+            unicode: locale.contains("utf-8") || locale.contains("utf8"),
+            synchronized_output: tigetstr("Sync"),
+            bracketed_paste: tigetstr("BE"),
+            osc52: tigetstr("Ms"),
+            term,
+        }
+    }
+
+    /// Sends XTVERSION, DECRQM and primary device attribute queries and
+    /// applies the replies that arrive within `timeout`. Returns the other
+    /// input that arrived in the meantime, such as keys typed while waiting.
+    ///
+    /// The primary device attributes query is sent last. Every terminal
+    /// answers it, so its reply marks the end of the replies and the wait
+    /// usually ends long before the timeout.
+    pub(crate) fn query(&mut self, timeout: Duration) -> Vec<u8> {
+        curses::send("\x1b[>0q\x1b[?2026$p\x1b[?2004$p\x1b[c");
+
+        let mut buf = vec![];
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            let before = buf.len();
+            curses::read_input(&mut buf);
+            if parse_replies(&buf).device_attributes.is_some() {
+                break;
+            }
+            if buf.len() == before {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        let replies = parse_replies(&buf);
+        self.apply(&replies);
+        replies.other
+    }
+
+    fn apply(&mut self, replies: &Replies) {
+        if let Some(version) = &replies.version {
+            self.version = Some(version.clone());
+        }
+        if let Some(supported) = replies.synchronized_output {
+            self.synchronized_output = supported;
+        }
+        if let Some(supported) = replies.bracketed_paste {
+            self.bracketed_paste = supported;
+        }
+        if let Some(attributes) = &replies.device_attributes {
+            self.osc52 |= attributes.contains(&52);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Replies {
+    version: Option<String>,
+    synchronized_output: Option<bool>,
+    bracketed_paste: Option<bool>,
+    device_attributes: Option<Vec<u32>>,
+    /// Everything in the input that isn't a reply, in order.
+    other: Vec<u8>,
+}
+
+/// Picks the replies to the queries sent by `Capabilities::query` out of the
+/// terminal's input, and keeps the rest of the input as it was.
+fn parse_replies(buf: &[u8]) -> Replies {
+    let mut replies = Replies::default();
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] != 0x1b || i + 1 >= buf.len() {
+            replies.other.push(buf[i]);
+            i += 1;
+            continue;
+        }
+
+        match buf[i + 1] {
+            // DCS > | text ST
+            b'P' => {
+                let body = &buf[i + 2..];
+                match body.windows(2).position(|w| w == b"\x1b\\") {
+                    Some(end) => {
+                        if body.starts_with(b">|") {
+                            replies.version = Some(String::from_utf8_lossy(&body[2..end]).into_owned());
+                        } else {
+                            replies.other.extend_from_slice(&buf[i..i + 2 + end + 2]);
+                        }
+                        i += 2 + end + 2;
+                    }
+                    None => {
+                        replies.other.extend_from_slice(&buf[i..]);
+                        break;
+                    }
+                }
+            }
+            // CSI ? params final
+            b'[' => {
+                let body = &buf[i + 2..];
+                let end = match body.iter().position(|&b| (0x40..=0x7e).contains(&b)) {
+                    Some(end) => end,
+                    None => {
+                        replies.other.extend_from_slice(&buf[i..]);
+                        break;
+                    }
+                };
+                let params = String::from_utf8_lossy(&body[..end]);
+                let is_reply = match params.strip_prefix('?') {
+                    Some(params) => match body[end] {
+                        b'c' => {
+                            replies.device_attributes =
+                                Some(params.split(';').filter_map(|p| p.parse().ok()).collect());
+                            true
+                        }
+                        b'y' => {
+                            let mut values = params.trim_end_matches('$').split(';');
+                            let mode = values.next().and_then(|v| v.parse::<u32>().ok());
+                            let state = values.next().and_then(|v| v.parse::<u32>().ok());
+                            let supported = state.map(|s| s != 0);
+                            match mode {
+                                Some(2026) => replies.synchronized_output = supported,
+                                Some(2004) => replies.bracketed_paste = supported,
+                                _ => {}
+                            }
+                            true
+                        }
+                        _ => false,
+                    },
+                    None => false,
+                };
+                if !is_reply {
+                    replies.other.extend_from_slice(&buf[i..i + 2 + end + 1]);
+                }
+                i += 2 + end + 1;
+            }
+            _ => {
+                replies.other.push(buf[i]);
+                i += 1;
+            }
+        }
+    }
+    replies
+}
+
+fn capname(name: &str) -> CString {
+    CString::new(name).unwrap()
+}
+
+fn tigetnum(name: &str) -> Option<u32> {
+    let name = capname(name);
+    let value = unsafe { curses::tigetnum(name.as_ptr() as *mut c_char) };
+    if value >= 0 {
+        Some(value as u32)
+    } else {
+        None
+    }
+}
+
+fn tigetflag(name: &str) -> bool {
+    let name = capname(name);
+    unsafe { curses::tigetflag(name.as_ptr() as *mut c_char) > 0 }
+}
+
+/// True if the terminal has the given string capability. `tigetstr` returns
+/// -1 cast to a pointer for names that aren't string capabilities.
+fn tigetstr(name: &str) -> bool {
+    let name = capname(name);
+    let value = unsafe { curses::tigetstr(name.as_ptr() as *mut c_char) };
+    if value.is_null() || value as isize == -1 {
+        return false;
+    }
+    !unsafe { CStr::from_ptr(value) }.to_bytes().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_reply() {
+        let replies = parse_replies(b"\x1bP>|XTerm(372)\x1b\\\x1b[?2026;2$y\x1b[?2004;1$y\x1b[?62;22;52c");
+        assert_eq!(replies.version.as_deref(), Some("XTerm(372)"));
+        assert_eq!(replies.synchronized_output, Some(true));
+        assert_eq!(replies.bracketed_paste, Some(true));
+        assert_eq!(replies.device_attributes, Some(vec![62, 22, 52]));
+        assert!(replies.other.is_empty());
+    }
+
+    #[test]
+    fn reads_unrecognized_modes_as_unsupported() {
+        let replies = parse_replies(b"\x1b[?2026;0$y\x1b[?2004;4$y\x1b[?1;2c");
+        assert_eq!(replies.synchronized_output, Some(false));
+        assert_eq!(replies.bracketed_paste, Some(true));
+        assert_eq!(replies.device_attributes, Some(vec![1, 2]));
+        assert_eq!(replies.version, None);
+    }
+
+    #[test]
+    fn keeps_other_input_in_order() {
+        let replies = parse_replies(b"a\x1b[A\x1b[?2004;2$yb\x1bPq\x1b\\\x1b[?1c\x1b");
+        assert_eq!(replies.bracketed_paste, Some(true));
+        assert_eq!(replies.device_attributes, Some(vec![1]));
+        assert_eq!(replies.other, b"a\x1b[Ab\x1bPq\x1b\\\x1b".to_vec());
+    }
+
+    #[test]
+    fn keeps_incomplete_replies_as_input() {
+        let replies = parse_replies(b"x\x1b[?2004;1");
+        assert_eq!(replies.bracketed_paste, None);
+        assert_eq!(replies.other, b"x\x1b[?2004;1".to_vec());
+
+        let replies = parse_replies(b"\x1bP>|XTerm");
+        assert_eq!(replies.version, None);
+        assert_eq!(replies.other, b"\x1bP>|XTerm".to_vec());
+    }
+
+    #[test]
+    fn applies_only_the_replies_that_arrived() {
+        let mut capabilities = Capabilities {
+            synchronized_output: true,
+            bracketed_paste: true,
+            ..Capabilities::default()
+        };
+        capabilities.apply(&parse_replies(b"\x1b[?2004;0$y\x1b[?62;52c"));
+        assert!(capabilities.synchronized_output);
+        assert!(!capabilities.bracketed_paste);
+        assert!(capabilities.osc52);
+        assert_eq!(capabilities.version, None);
+
+        capabilities.apply(&parse_replies(b"\x1b[?62c"));
+        assert!(capabilities.osc52);
+    }
+}
@@ -0,0 +1,437 @@
+use imgui;
+use std::ffi::{CStr, CString};
+use std::io::Write;
+use std::os::raw::{c_char, c_void};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// An external clipboard, tried before OSC 52 and the in-process buffer.
+pub trait ClipboardProvider {
+    /// Stores `text`, returning false if it couldn't.
+    fn copy(&mut self, text: &str) -> bool;
+    /// Returns the clipboard contents, or `None` if they couldn't be read.
+    fn paste(&mut self) -> Option<String>;
+}
+
+/// A clipboard backed by external commands, e.g. `wl-copy` and `wl-paste` or
+/// `xclip -selection clipboard` and `xclip -selection clipboard -o`.
+///
+/// Copied text is written to the copy command's stdin, and pasted text is
+/// read from the paste command's stdout.
+#[derive(Clone, Debug, Default)]
+pub struct CommandClipboard {
+    pub copy: Vec<String>,
+    pub paste: Vec<String>,
+}
+
+impl CommandClipboard {
+    pub fn new(copy: &[&str], paste: &[&str]) -> CommandClipboard {
+        CommandClipboard {
+            copy: copy.iter().map(|s| s.to_string()).collect(),
+            paste: paste.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl ClipboardProvider for CommandClipboard {
+    fn copy(&mut self, text: &str) -> bool {
+        let (program, args) = match self.copy.split_first() {
+            Some(command) => command,
+            None => return false,
+        };
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => return false,
+        };
+        if let Some(stdin) = child.stdin.as_mut() {
+            if stdin.write_all(text.as_bytes()).is_err() {
+                return false;
+            }
+        }
+        drop(child.stdin.take());
+        child.wait().map(|status| status.success()).unwrap_or(false)
+    }
+
+    fn paste(&mut self) -> Option<String> {
+        let (program, args) = self.paste.split_first()?;
+        let output = Command::new(program).args(args).stderr(Stdio::null()).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+}
+
+/// How long to wait for the terminal to answer an OSC 52 read.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A terminal the clipboard can write to and read from right away, which
+/// OSC 52 reads need to wait for the reply.
+pub(crate) trait ClipboardLink {
+    fn send(&mut self, bytes: &[u8]);
+    /// Appends the input that is immediately available to `buf`.
+    fn read_input(&mut self, buf: &mut Vec<u8>);
+}
+
+/// The imgui clipboard backend installed by every backend.
+///
+/// Copies go to the provider if there is one, to the terminal via OSC 52, and
+/// to an in-process buffer. Pastes come from the provider, then from an OSC 52
+/// query, and then from the buffer. Terminals that don't answer the query
+/// within a short timeout aren't asked again.
+///
+/// Without a link, OSC 52 copies are queued and written with the next frame,
+/// and the terminal's clipboard can't be read.
+pub(crate) struct Clipboard {
+    buffer: String,
+    osc52_write: bool,
+    osc52_read: bool,
+    /// Set once the app chose the OSC 52 settings, which then win over what
+    /// the terminal advertises.
+    osc52_configured: bool,
+    provider: Option<Box<dyn ClipboardProvider>>,
+    link: Option<Box<dyn ClipboardLink>>,
+    /// OSC 52 copies waiting to be written without a link.
+    queued_output: Vec<u8>,
+    /// Input that arrived while waiting for an OSC 52 reply, to be handed to
+    /// the input parser on the next frame.
+    stashed_input: Vec<u8>,
+    text: CString,
+}
+
+impl Clipboard {
+    /// A clipboard that copies with OSC 52, but only reads the terminal's
+    /// clipboard once `set_osc52` or `detected_osc52` allow it.
+    pub fn new() -> Box<Clipboard> {
+        Box::new(Clipboard {
+            buffer: String::new(),
+            osc52_write: true,
+            osc52_read: false,
+            osc52_configured: false,
+            provider: None,
+            link: None,
+            queued_output: vec![],
+            stashed_input: vec![],
+            text: CString::default(),
+        })
+    }
+
+    /// Points the clipboard callbacks of the current imgui context at this
+    /// clipboard, which is why it is kept boxed. It must not move until
+    /// `uninstall` is called.
+    pub fn install(&mut self) {
+        unsafe {
+            let io = &mut *imgui::sys::igGetIO();
+            io.SetClipboardTextFn = Some(set_clipboard_text);
+            io.GetClipboardTextFn = Some(get_clipboard_text);
+            io.ClipboardUserData = self as *mut Clipboard as *mut c_void;
+        }
+    }
+
+    pub fn uninstall(&mut self) {
+        unsafe {
+            if imgui::sys::igGetCurrentContext().is_null() {
+                return;
+            }
+            let io = &mut *imgui::sys::igGetIO();
+            if io.ClipboardUserData == self as *mut Clipboard as *mut c_void {
+                io.SetClipboardTextFn = None;
+                io.GetClipboardTextFn = None;
+                io.ClipboardUserData = std::ptr::null_mut();
+            }
+        }
+    }
+
+    pub fn set_osc52(&mut self, write: bool, read: bool) {
+        self.osc52_write = write;
+        self.osc52_read = read;
+        self.osc52_configured = true;
+    }
+
+    /// Reads the terminal's clipboard if the terminal says it supports OSC
+    /// 52, unless the app chose otherwise with `set_osc52`.
+    pub fn detected_osc52(&mut self, supported: bool) {
+        if !self.osc52_configured {
+            self.osc52_read = supported;
+        }
+    }
+
+    pub fn set_link(&mut self, link: Option<Box<dyn ClipboardLink>>) {
+        self.link = link;
+    }
+
+    /// Moves the queued OSC 52 copies to `out`.
+    pub fn take_output(&mut self, out: &mut Vec<u8>) {
+        out.append(&mut self.queued_output);
+    }
+
+    pub fn set_provider(&mut self, provider: Option<Box<dyn ClipboardProvider>>) {
+        self.provider = provider;
+    }
+
+    pub fn take_stashed_input(&mut self, buf: &mut Vec<u8>) {
+        buf.append(&mut self.stashed_input);
+    }
+
+    pub fn set(&mut self, text: &str) {
+        self.buffer = text.to_string();
+        if let Some(provider) = &mut self.provider {
+            if provider.copy(text) {
+                return;
+            }
+        }
+        if self.osc52_write {
+            let seq = format!("\x1b]52;c;{}\x07", encode_base64(text.as_bytes()));
+            match &mut self.link {
+                Some(link) => link.send(seq.as_bytes()),
+                None => self.queued_output.extend_from_slice(seq.as_bytes()),
+            }
+        }
+    }
+
+    pub fn get(&mut self) -> String {
+        if let Some(provider) = &mut self.provider {
+            if let Some(text) = provider.paste() {
+                return text;
+            }
+        }
+        if self.osc52_read && self.link.is_some() {
+            match self.query() {
+                Some(Some(text)) => return text,
+                // The terminal answered, but with nothing it would share.
+                Some(None) => {}
+                None => self.osc52_read = false,
+            }
+        }
+        self.buffer.clone()
+    }
+
+    /// Asks the terminal for its clipboard, keeping any other input that
+    /// arrives in the meantime. Returns `None` if no reply arrived in time,
+    /// and `Some(None)` for a reply without any text.
+    fn query(&mut self) -> Option<Option<String>> {
+        let link = self.link.as_mut()?;
+        link.send(b"\x1b]52;c;?\x07");
+
+        let mut buf = vec![];
+        let deadline = Instant::now() + READ_TIMEOUT;
+        while Instant::now() < deadline {
+            let before = buf.len();
+            link.read_input(&mut buf);
+            if let Some((start, end, text)) = find_reply(&buf) {
+                self.stashed_input.extend_from_slice(&buf[..start]);
+                self.stashed_input.extend_from_slice(&buf[end..]);
+                return Some(text);
+            }
+            if buf.len() == before {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+        self.stashed_input.extend_from_slice(&buf);
+        None
+    }
+}
+
+unsafe extern "C" fn set_clipboard_text(user_data: *mut c_void, text: *const c_char) {
+    let clipboard = &mut *(user_data as *mut Clipboard);
+    let text = CStr::from_ptr(text).to_string_lossy();
+    clipboard.set(&text);
+}
+
+unsafe extern "C" fn get_clipboard_text(user_data: *mut c_void) -> *const c_char {
+    let clipboard = &mut *(user_data as *mut Clipboard);
+    let text = clipboard.get().replace('\0', "");
+    clipboard.text = CString::new(text).unwrap_or_default();
+    clipboard.text.as_ptr()
+}
+
+/// Finds an OSC 52 reply, terminated by BEL or ST, in `buf`. Returns where it
+/// starts and ends, and the decoded text if it held any.
+fn find_reply(buf: &[u8]) -> Option<(usize, usize, Option<String>)> {
+    let prefix = b"\x1b]52;";
+    let start = buf.windows(prefix.len()).position(|w| w == prefix)?;
+    let body = &buf[start + prefix.len()..];
+    let (len, terminator) = match body.iter().position(|&b| b == 0x07) {
+        Some(bel) => (bel, 1),
+        None => (body.windows(2).position(|w| w == b"\x1b\\")?, 2),
+    };
+    let body = &body[..len];
+    let data = match body.iter().position(|&b| b == b';') {
+        Some(semicolon) => &body[semicolon + 1..],
+        None => body,
+    };
+    let text = decode_base64(data).and_then(|bytes| String::from_utf8(bytes).ok());
+    Some((start, start + prefix.len() + len + terminator, text))
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for &b in data {
+        let value = match b {
+            b'=' => break,
+            b'\r' | b'\n' => continue,
+            _ => BASE64.iter().position(|&c| c == b)? as u32,
+        };
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// A terminal that answers reads with the given chunks, one per read.
+    struct FakeLink {
+        sent: Rc<RefCell<Vec<u8>>>,
+        reads: VecDeque<Vec<u8>>,
+    }
+
+    impl ClipboardLink for FakeLink {
+        fn send(&mut self, bytes: &[u8]) {
+            self.sent.borrow_mut().extend_from_slice(bytes);
+        }
+
+        fn read_input(&mut self, buf: &mut Vec<u8>) {
+            if let Some(chunk) = self.reads.pop_front() {
+                buf.extend_from_slice(&chunk);
+            }
+        }
+    }
+
+    fn linked(reads: &[&[u8]]) -> (Box<Clipboard>, Rc<RefCell<Vec<u8>>>) {
+        let sent = Rc::new(RefCell::new(vec![]));
+        let mut clipboard = Clipboard::new();
+        clipboard.set_link(Some(Box::new(FakeLink {
+            sent: sent.clone(),
+            reads: reads.iter().map(|chunk| chunk.to_vec()).collect(),
+        })));
+        clipboard.set_osc52(true, true);
+        (clipboard, sent)
+    }
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        let cases: &[(&str, &str)] = &[
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for &(text, encoded) in cases {
+            assert_eq!(encode_base64(text.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded.as_bytes()), Some(text.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn round_trips_every_byte() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 253..=255 {
+            assert_eq!(decode_base64(encode_base64(&bytes[..len]).as_bytes()), Some(bytes[..len].to_vec()));
+        }
+    }
+
+    #[test]
+    fn decodes_base64_across_line_breaks_and_rejects_garbage() {
+        assert_eq!(decode_base64(b"Zm9v\r\nYmFy"), Some(b"foobar".to_vec()));
+        assert_eq!(decode_base64(b"Zm9v!"), None);
+        assert_eq!(decode_base64(b"?"), None);
+    }
+
+    #[test]
+    fn finds_replies_terminated_by_bel_or_st() {
+        let buf = b"a\x1b]52;c;aGk=\x07b";
+        assert_eq!(find_reply(buf), Some((1, 13, Some("hi".to_string()))));
+        let buf = b"\x1b]52;c;aGk=\x1b\\b";
+        assert_eq!(find_reply(buf), Some((0, 13, Some("hi".to_string()))));
+        // Terminals that won't share the clipboard answer without text.
+        assert_eq!(find_reply(b"\x1b]52;c;?\x07"), Some((0, 9, None)));
+        assert_eq!(find_reply(b"\x1b]52;c;aGk="), None);
+        assert_eq!(find_reply(b"\x1b]52;c;aGk=\x1b"), None);
+    }
+
+    #[test]
+    fn reads_replies_split_across_reads_and_keeps_other_input() {
+        let (mut clipboard, sent) = linked(&[b"x\x1b]5", b"2;c;aGVs", b"bG8=\x1b", b"\\y"]);
+        assert_eq!(clipboard.get(), "hello");
+        assert_eq!(&*sent.borrow(), b"\x1b]52;c;?\x07");
+
+        let mut stashed = vec![];
+        clipboard.take_stashed_input(&mut stashed);
+        assert_eq!(stashed, b"xy".to_vec());
+    }
+
+    #[test]
+    fn falls_back_to_the_buffer_when_the_terminal_refuses() {
+        let (mut clipboard, _) = linked(&[b"\x1b]52;c;?\x07"]);
+        clipboard.set_osc52(false, true);
+        clipboard.set("copied");
+        assert_eq!(clipboard.get(), "copied");
+        assert!(clipboard.osc52_read);
+    }
+
+    #[test]
+    fn stops_reading_after_a_timeout() {
+        let (mut clipboard, sent) = linked(&[b"typed"]);
+        clipboard.set_osc52(false, true);
+        clipboard.set("copied");
+        assert_eq!(clipboard.get(), "copied");
+        assert!(!clipboard.osc52_read);
+
+        // The terminal isn't asked again.
+        assert_eq!(clipboard.get(), "copied");
+        assert_eq!(&*sent.borrow(), b"\x1b]52;c;?\x07");
+        let mut stashed = vec![];
+        clipboard.take_stashed_input(&mut stashed);
+        assert_eq!(stashed, b"typed".to_vec());
+    }
+
+    #[test]
+    fn queues_copies_without_a_link() {
+        let mut clipboard = Clipboard::new();
+        clipboard.set("hi");
+        let mut out = vec![];
+        clipboard.take_output(&mut out);
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07".to_vec());
+        // Without a link the terminal's clipboard can't be read.
+        clipboard.detected_osc52(true);
+        assert_eq!(clipboard.get(), "hi");
+    }
+}
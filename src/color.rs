@@ -0,0 +1,368 @@
+use imgui;
+use imgui::internal::RawCast;
+
+/// Quantizes an imgui color to an index in the xterm 256 color palette.
+///
+/// This mirrors the conversion done by the `imtui` text rasterizer, so the
+/// result matches the color index stored in the screen cells. Text colors are
+/// quantized without alpha, filled shapes are premultiplied by their alpha.
+pub fn ansi256(color: [f32; 4], use_alpha: bool) -> u8 {
+    let (mut r, mut g, mut b) = (to_u8(color[0]), to_u8(color[1]), to_u8(color[2]));
+    if use_alpha {
+        let scale = to_u8(color[3]) as f32 / 255.0;
+        r = (r as f32 * scale).round() as u8;
+        g = (g as f32 * scale).round() as u8;
+        b = (b as f32 * scale).round() as u8;
+    }
+
+    if r == g && g == b {
+        if r < 8 {
+            return 16;
+        }
+        if r > 248 {
+            return 231;
+        }
+        return (((r as f32 - 8.0) / 247.0) * 24.0).round() as u8 + 232;
+    }
+
+    let level = |x: u8| (x as f32 / 255.0 * 5.0).round() as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// Converts a color channel the way imgui packs colors into vertices.
+fn to_u8(x: f32) -> u8 {
+    (x.max(0.0).min(1.0) * 255.0 + 0.5) as u8
+}
+
+/// Packs a color like imgui's `IM_COL32`, red in the low byte.
+fn pack(color: [f32; 4]) -> u32 {
+    color.iter().enumerate().fold(0, |packed, (i, &x)| packed | (to_u8(x) as u32) << (8 * i))
+}
+
+fn unpack(packed: u32) -> [f32; 4] {
+    let channel = |i: u32| (packed >> (8 * i) & 0xff) as f32 / 255.0;
+    [channel(0), channel(1), channel(2), channel(3)]
+}
+
+/// An opaque color that `ansi256` quantizes to `index`, with or without
+/// alpha, if there is one.
+fn color_for_index(index: u8) -> Option<[f32; 4]> {
+    let color = match index {
+        16..=231 => {
+            let i = index - 16;
+            let level = |x: u8| x as f32 / 5.0;
+            [level(i / 36), level(i / 6 % 6), level(i % 6), 1.0]
+        }
+        232..=255 => {
+            let gray = (8.0 + (index - 232) as f32 * 247.0 / 24.0) / 255.0;
+            [gray, gray, gray, 1.0]
+        }
+        _ => return None,
+    };
+    if ansi256(color, false) == index && ansi256(color, true) == index {
+        Some(color)
+    } else {
+        None
+    }
+}
+
+/// The xterm default for the 16 system colors.
+const SYSTEM_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0xcd, 0x00, 0x00], [0x00, 0xcd, 0x00], [0xcd, 0xcd, 0x00],
+    [0x00, 0x00, 0xee], [0xcd, 0x00, 0xcd], [0x00, 0xcd, 0xcd], [0xe5, 0xe5, 0xe5],
+    [0x7f, 0x7f, 0x7f], [0xff, 0x00, 0x00], [0x00, 0xff, 0x00], [0xff, 0xff, 0x00],
+    [0x5c, 0x5c, 0xff], [0xff, 0x00, 0xff], [0x00, 0xff, 0xff], [0xff, 0xff, 0xff],
+];
+
+/// Returns the RGB value of a color in the xterm 256 color palette, with the
+/// xterm defaults for the 16 system colors, which terminals let users change.
+pub fn palette_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => SYSTEM_COLORS[index as usize],
+        16..=231 => {
+            let level = |x: u8| if x == 0 { 0 } else { 55 + 40 * x };
+            let i = index - 16;
+            [level(i / 36), level(i / 6 % 6), level(i % 6)]
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            [gray, gray, gray]
+        }
+    }
+}
+
+/// Colors that are drawn with the terminal's own default foreground and
+/// background instead of an explicit palette color.
+///
+/// Only text and shapes drawn in exactly the given colors are passed through,
+/// not other colors that quantize to the same palette index. When a
+/// background is set, cells not covered by any imgui window also keep the
+/// terminal default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DefaultColors {
+    pub foreground: Option<[f32; 4]>,
+    pub background: Option<[f32; 4]>,
+}
+
+impl DefaultColors {
+    /// Passes through the style's `Text` and `WindowBg` colors, so windows
+    /// blend into the terminal theme.
+    pub fn from_style(style: &imgui::Style) -> DefaultColors {
+        DefaultColors {
+            foreground: Some(style[imgui::StyleColor::Text]),
+            background: Some(style[imgui::StyleColor::WindowBg]),
+        }
+    }
+
+    /// Redraws the vertices of `draw_data` that have exactly the default
+    /// colors in palette colors that nothing else in the frame quantizes to,
+    /// and returns those palette indices. Call it right before rasterizing.
+    ///
+    /// If the frame leaves no palette index free, cells are matched by the
+    /// quantized default colors instead.
+    pub(crate) fn mark(&self, draw_data: &imgui::DrawData) -> ResolvedColors {
+        let foreground = self.foreground.map(pack);
+        let background = self.background.map(pack);
+        if foreground.is_none() && background.is_none() {
+            return ResolvedColors::default();
+        }
+        let vertices = vertex_buffers(draw_data);
+
+        // Text and shapes quantize differently, so both ways count as used.
+        let mut used = [false; 256];
+        for &(data, len) in &vertices {
+            let vertices = unsafe { std::slice::from_raw_parts(data, len) };
+            for vertex in vertices {
+                if Some(vertex.col) != foreground && Some(vertex.col) != background {
+                    let color = unpack(vertex.col);
+                    used[ansi256(color, false) as usize] = true;
+                    used[ansi256(color, true) as usize] = true;
+                }
+            }
+        }
+        let mut free = (16..=255u8)
+            .rev()
+            .filter(|&i| !used[i as usize])
+            .filter_map(|i| color_for_index(i).map(|color| (i, color)));
+        let foreground = match foreground {
+            Some(packed) => match free.next() {
+                Some((index, color)) => Some((packed, index, pack(color))),
+                None => return self.quantize(),
+            },
+            None => None,
+        };
+        let background = match background {
+            Some(packed) if Some(packed) == foreground.map(|(packed, _, _)| packed) => foreground,
+            Some(packed) => match free.next() {
+                Some((index, color)) => Some((packed, index, pack(color))),
+                None => return self.quantize(),
+            },
+            None => None,
+        };
+
+        for &(data, len) in &vertices {
+            let vertices = unsafe { std::slice::from_raw_parts_mut(data, len) };
+            for vertex in vertices {
+                for &(packed, _, marker) in foreground.iter().chain(background.iter()) {
+                    if vertex.col == packed {
+                        vertex.col = marker;
+                        break;
+                    }
+                }
+            }
+        }
+        ResolvedColors {
+            foreground: foreground.map(|(_, index, _)| index),
+            background: background.map(|(_, index, _)| index),
+        }
+    }
+
+    /// The default colors as palette indices.
+    fn quantize(&self) -> ResolvedColors {
+        ResolvedColors {
+            foreground: self.foreground.map(|c| ansi256(c, false)),
+            background: self.background.map(|c| ansi256(c, true)),
+        }
+    }
+}
+
+/// The vertex buffers of every draw list, which imgui owns until the next
+/// frame.
+fn vertex_buffers(draw_data: &imgui::DrawData) -> Vec<(*mut imgui::sys::ImDrawVert, usize)> {
+    let mut buffers = vec![];
+    unsafe {
+        let raw = draw_data.raw();
+        if raw.CmdLists.is_null() {
+            return buffers;
+        }
+        for i in 0..raw.CmdListsCount.max(0) as usize {
+            let list = &**raw.CmdLists.add(i);
+            if list.VtxBuffer.Size > 0 && !list.VtxBuffer.Data.is_null() {
+                buffers.push((list.VtxBuffer.Data, list.VtxBuffer.Size as usize));
+            }
+        }
+    }
+    buffers
+}
+
+/// `DefaultColors` quantized to palette indices.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ResolvedColors {
+    pub foreground: Option<u8>,
+    pub background: Option<u8>,
+}
+
+impl ResolvedColors {
+    /// Returns the foreground and background palette indices of a cell, with
+    /// `None` standing for the terminal default.
+    pub fn apply(&self, cell: crate::screen::Cell) -> (Option<u8>, Option<u8>) {
+        let fg = match self.foreground {
+            Some(index) if index == cell.fg => None,
+            _ => Some(cell.fg),
+        };
+        let bg = match self.background {
+            Some(_) if cell.is_blank() => None,
+            Some(index) if index == cell.bg => None,
+            _ => Some(cell.bg),
+        };
+        (fg, bg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::Cell;
+    use imgui::internal::RawCast;
+    use imgui::sys::{ImDrawData, ImDrawList, ImDrawVert, ImVec2};
+    use std::mem;
+
+    /// Draw data with a single draw list holding one vertex per color.
+    struct Frame {
+        vertices: Vec<ImDrawVert>,
+        list: Box<ImDrawList>,
+        lists: Vec<*mut ImDrawList>,
+        raw: Box<ImDrawData>,
+    }
+
+    impl Frame {
+        fn new(colors: &[[f32; 4]]) -> Frame {
+            let origin = ImVec2 { x: 0.0, y: 0.0 };
+            let vertices = colors
+                .iter()
+                .map(|&color| ImDrawVert {
+                    pos: origin,
+                    uv: origin,
+                    col: pack(color),
+                })
+                .collect();
+            let mut frame = Frame {
+                vertices,
+                list: Box::new(unsafe { mem::zeroed() }),
+                lists: vec![],
+                raw: Box::new(unsafe { mem::zeroed() }),
+            };
+            frame.list.VtxBuffer.Size = frame.vertices.len() as i32;
+            frame.list.VtxBuffer.Capacity = frame.vertices.len() as i32;
+            frame.list.VtxBuffer.Data = frame.vertices.as_mut_ptr();
+            frame.lists.push(&mut *frame.list as *mut ImDrawList);
+            frame.raw.CmdLists = frame.lists.as_mut_ptr();
+            frame.raw.CmdListsCount = 1;
+            frame
+        }
+
+        fn draw_data(&self) -> &imgui::DrawData {
+            unsafe { imgui::DrawData::from_raw(&*self.raw) }
+        }
+
+        fn color(&self, i: usize) -> u32 {
+            self.vertices[i].col
+        }
+    }
+
+    const TEXT: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+    const WINDOW: [f32; 4] = [0.1, 0.1, 0.2, 1.0];
+    const OTHER: [f32; 4] = [0.91, 0.9, 0.9, 1.0];
+
+    #[test]
+    fn quantizes_like_the_rasterizer() {
+        assert_eq!(ansi256([0.0, 0.0, 0.0, 1.0], false), 16);
+        assert_eq!(ansi256([1.0, 1.0, 1.0, 1.0], false), 231);
+        assert_eq!(ansi256([1.0, 0.0, 0.0, 1.0], false), 196);
+        assert_eq!(ansi256([0.5, 0.5, 0.5, 1.0], false), 244);
+        assert_eq!(ansi256([1.0, 1.0, 1.0, 0.0], false), 231);
+        assert_eq!(ansi256([1.0, 1.0, 1.0, 0.0], true), 16);
+    }
+
+    #[test]
+    fn looks_up_palette_colors() {
+        assert_eq!(palette_rgb(0), [0x00, 0x00, 0x00]);
+        assert_eq!(palette_rgb(9), [0xff, 0x00, 0x00]);
+        assert_eq!(palette_rgb(15), [0xff, 0xff, 0xff]);
+        assert_eq!(palette_rgb(16), [0, 0, 0]);
+        assert_eq!(palette_rgb(21), [0, 0, 255]);
+        assert_eq!(palette_rgb(67), [95, 135, 175]);
+        assert_eq!(palette_rgb(196), [255, 0, 0]);
+        assert_eq!(palette_rgb(231), [255, 255, 255]);
+        assert_eq!(palette_rgb(232), [8, 8, 8]);
+        assert_eq!(palette_rgb(255), [238, 238, 238]);
+    }
+
+    #[test]
+    fn leaves_frames_alone_without_default_colors() {
+        let frame = Frame::new(&[TEXT, WINDOW]);
+        let resolved = DefaultColors::default().mark(frame.draw_data());
+        assert_eq!(resolved, ResolvedColors::default());
+        assert_eq!((frame.color(0), frame.color(1)), (pack(TEXT), pack(WINDOW)));
+    }
+
+    #[test]
+    fn marks_only_exact_default_colors_with_free_indices() {
+        // OTHER quantizes to the same index as TEXT but must not pass through.
+        let other_index = ansi256(OTHER, false);
+        let frame = Frame::new(&[TEXT, WINDOW, OTHER]);
+        let colors = DefaultColors {
+            foreground: Some(TEXT),
+            background: Some(WINDOW),
+        };
+        let resolved = colors.mark(frame.draw_data());
+
+        let foreground = resolved.foreground.unwrap();
+        let background = resolved.background.unwrap();
+        assert_ne!(foreground, background);
+        for &index in &[foreground, background] {
+            assert_ne!(index, other_index);
+            assert_ne!(index, ansi256(OTHER, true));
+        }
+        let marked = |i: usize| unpack(frame.color(i));
+        assert_eq!(ansi256(marked(0), false), foreground);
+        assert_eq!(ansi256(marked(1), true), background);
+        assert_eq!(frame.color(2), pack(OTHER));
+    }
+
+    #[test]
+    fn shares_one_index_when_both_defaults_match() {
+        let frame = Frame::new(&[TEXT]);
+        let colors = DefaultColors {
+            foreground: Some(TEXT),
+            background: Some(TEXT),
+        };
+        let resolved = colors.mark(frame.draw_data());
+        assert!(resolved.foreground.is_some());
+        assert_eq!(resolved.foreground, resolved.background);
+    }
+
+    #[test]
+    fn applies_defaults_to_cells() {
+        let resolved = ResolvedColors {
+            foreground: Some(254),
+            background: Some(17),
+        };
+        let cell = |fg, bg| Cell { ch: 'a' as u16, fg, bg };
+        assert_eq!(resolved.apply(cell(254, 17)), (None, None));
+        assert_eq!(resolved.apply(cell(196, 17)), (Some(196), None));
+        assert_eq!(resolved.apply(cell(254, 21)), (None, Some(21)));
+        assert_eq!(resolved.apply(Cell::default()), (Some(0), None));
+        assert_eq!(ResolvedColors::default().apply(Cell::default()), (Some(0), Some(0)));
+    }
+}
@@ -0,0 +1,226 @@
+//! Creating an imgui context that suits a grid of terminal cells.
+
+use crate::theme::Metrics;
+use imgui;
+
+/// A named look for a terminal UI: the cell-sized spacing that every preset
+/// shares, and a set of colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StylePreset {
+    /// imgui's dark colors.
+    Dark,
+    /// imgui's light colors.
+    Light,
+    /// imgui's classic blue and grey colors.
+    Classic,
+    /// Black text on off-white, with orange title bars.
+    Paper,
+    /// Green text on black.
+    Retro,
+}
+
+impl StylePreset {
+    pub const ALL: [StylePreset; 5] = [
+        StylePreset::Dark,
+        StylePreset::Light,
+        StylePreset::Classic,
+        StylePreset::Paper,
+        StylePreset::Retro,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StylePreset::Dark => "dark",
+            StylePreset::Light => "light",
+            StylePreset::Classic => "classic",
+            StylePreset::Paper => "paper",
+            StylePreset::Retro => "retro",
+        }
+    }
+
+    /// The preset called `name`, as returned by `name`.
+    pub fn from_name(name: &str) -> Option<StylePreset> {
+        StylePreset::ALL.iter().cloned().find(|preset| preset.name() == name)
+    }
+
+    /// Sets the spacing and the colors of `style`.
+    pub fn apply(self, style: &mut imgui::Style) {
+        apply_terminal_metrics(style);
+        match self {
+            StylePreset::Dark => {
+                style.use_dark_colors();
+            }
+            StylePreset::Light => {
+                style.use_light_colors();
+            }
+            StylePreset::Classic => {
+                style.use_classic_colors();
+            }
+            StylePreset::Paper => {
+                style.use_light_colors();
+                set_colors(style, &PAPER_COLORS);
+            }
+            StylePreset::Retro => {
+                style.use_dark_colors();
+                set_colors(style, &RETRO_COLORS);
+            }
+        }
+    }
+}
+
+impl Default for StylePreset {
+    fn default() -> StylePreset {
+        StylePreset::Dark
+    }
+}
+
+const PAPER_COLORS: [(imgui::StyleColor, [f32; 4]); 9] = [
+    (imgui::StyleColor::Text, [0.0, 0.0, 0.0, 1.0]),
+    (imgui::StyleColor::TextDisabled, [0.6, 0.6, 0.6, 1.0]),
+    (imgui::StyleColor::WindowBg, [0.96, 0.96, 0.94, 1.0]),
+    (imgui::StyleColor::TitleBg, [1.0, 0.4, 0.0, 1.0]),
+    (imgui::StyleColor::TitleBgActive, [1.0, 0.4, 0.0, 1.0]),
+    (imgui::StyleColor::TitleBgCollapsed, [0.69, 0.25, 0.0, 1.0]),
+    (imgui::StyleColor::ChildBg, [0.96, 0.96, 0.94, 1.0]),
+    (imgui::StyleColor::PopupBg, [0.96, 0.96, 0.94, 1.0]),
+    (imgui::StyleColor::ModalWindowDimBg, [0.0, 0.0, 0.0, 0.0]),
+];
+
+const RETRO_COLORS: [(imgui::StyleColor, [f32; 4]); 9] = [
+    (imgui::StyleColor::Text, [0.0, 1.0, 0.0, 1.0]),
+    (imgui::StyleColor::TextDisabled, [0.6, 0.6, 0.6, 1.0]),
+    (imgui::StyleColor::WindowBg, [0.0, 0.0, 0.0, 1.0]),
+    (imgui::StyleColor::TitleBg, [0.1, 0.2, 0.1, 1.0]),
+    (imgui::StyleColor::TitleBgActive, [0.1, 0.2, 0.1, 1.0]),
+    (imgui::StyleColor::TitleBgCollapsed, [0.5, 1.0, 0.5, 1.0]),
+    (imgui::StyleColor::ChildBg, [0.0, 0.0, 0.0, 1.0]),
+    (imgui::StyleColor::PopupBg, [0.0, 0.1, 0.0, 1.0]),
+    (imgui::StyleColor::ModalWindowDimBg, [0.0, 0.0, 0.0, 0.0]),
+];
+
+fn set_colors(style: &mut imgui::Style, colors: &[(imgui::StyleColor, [f32; 4])]) {
+    for (style_color, values) in colors {
+        style[*style_color] = *values;
+    }
+}
+
+/// Replaces imgui's pixel sizes with sizes in cells: one cell of spacing
+/// between items, no rounding, no borders and a one-cell scrollbar. The
+/// spacing, borders and scrollbar are the default `theme::Metrics`.
+pub fn apply_terminal_metrics(style: &mut imgui::Style) {
+    Metrics::default().apply(style);
+    style.alpha = 1.0;
+    style.window_rounding = 0.0;
+    style.window_min_size = [4.0, 2.0];
+    style.window_title_align = [0.0, 0.0];
+    style.child_rounding = 0.0;
+    style.popup_rounding = 0.0;
+    style.frame_rounding = 0.0;
+    style.touch_extra_padding = [0.5, 0.0];
+    style.columns_min_spacing = 1.0;
+    style.scrollbar_rounding = 0.0;
+    style.grab_min_size = 1.0;
+    style.grab_rounding = 0.0;
+    style.tab_rounding = 0.0;
+    style.tab_border_size = 0.0;
+    style.display_window_padding = [0.0, 0.0];
+    style.display_safe_area_padding = [0.0, 0.0];
+    style.anti_aliased_lines = false;
+    style.anti_aliased_fill = false;
+}
+
+/// Builds an `imgui::Context` for a terminal, see `imtui::context()`.
+#[derive(Clone, Debug)]
+pub struct ContextBuilder {
+    preset: StylePreset,
+    ini_filename: Option<imgui::ImString>,
+    scrollbar_size: Option<f32>,
+}
+
+impl ContextBuilder {
+    pub fn new() -> ContextBuilder {
+        ContextBuilder {
+            preset: StylePreset::default(),
+            ini_filename: None,
+            scrollbar_size: None,
+        }
+    }
+
+    pub fn preset(mut self, preset: StylePreset) -> ContextBuilder {
+        self.preset = preset;
+        self
+    }
+
+    /// Saves window positions and sizes to `filename`. Without it, nothing is
+    /// written, since an `imgui.ini` in the working directory is rarely
+    /// wanted by a command line program.
+    pub fn ini_filename<T: Into<Option<imgui::ImString>>>(mut self, filename: T) -> ContextBuilder {
+        self.ini_filename = filename.into();
+        self
+    }
+
+    /// The width of scrollbars in cells, one by default.
+    pub fn scrollbar_size(mut self, size: f32) -> ContextBuilder {
+        self.scrollbar_size = Some(size);
+        self
+    }
+
+    pub fn build(self) -> imgui::Context {
+        let mut context = imgui::Context::create();
+        context.set_ini_filename(self.ini_filename);
+        let style = context.style_mut();
+        self.preset.apply(style);
+        if let Some(size) = self.scrollbar_size {
+            style.scrollbar_size = size;
+        }
+        context
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn finds_presets_by_name() {
+        for &preset in StylePreset::ALL.iter() {
+            assert_eq!(StylePreset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(StylePreset::from_name("Dark"), None);
+        assert_eq!(StylePreset::default(), StylePreset::Dark);
+    }
+
+    #[test]
+    fn builds_contexts_with_cell_metrics_and_preset_colors() {
+        let _lock = test_util::imgui_lock();
+        for &preset in StylePreset::ALL.iter() {
+            let mut context = ContextBuilder::new().preset(preset).build();
+            let style = context.style_mut();
+            assert_eq!(style.item_spacing, [1.0, 0.0], "{:?}", preset);
+            assert_eq!(style.window_rounding, 0.0, "{:?}", preset);
+            assert_eq!(style.scrollbar_size, 1.0, "{:?}", preset);
+            match preset {
+                StylePreset::Paper => assert_eq!(style[imgui::StyleColor::Text], [0.0, 0.0, 0.0, 1.0]),
+                StylePreset::Retro => assert_eq!(style[imgui::StyleColor::Text], [0.0, 1.0, 0.0, 1.0]),
+                _ => {}
+            }
+            assert!(context.ini_filename().is_none());
+        }
+    }
+
+    #[test]
+    fn overrides_the_scrollbar_size() {
+        let _lock = test_util::imgui_lock();
+        let mut context = crate::context().preset(StylePreset::Light).scrollbar_size(2.0).build();
+        let style = context.style_mut();
+        assert_eq!(style.scrollbar_size, 2.0);
+        assert_eq!(style.frame_padding, [1.0, 0.0]);
+    }
+}
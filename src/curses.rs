@@ -0,0 +1,336 @@
+//! The parts of ncurses that the Rust side of the backend talks to directly.
+//!
+//! `imtui-sys` links against ncurses, so these are resolved from the same
+//! library the `imtui` C++ backend uses.
+
+use crate::clipboard::ClipboardLink;
+use crate::color::ResolvedColors;
+use crate::screen::{self, Cell};
+use crate::sys;
+use crate::tty::{TtyFds, TtySource};
+use libc;
+use std::collections::HashMap;
+use std::io;
+use std::os::raw::{c_char, c_int, c_short, c_void};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[allow(non_camel_case_types)]
+pub(crate) type WINDOW = c_void;
+#[allow(non_camel_case_types)]
+pub(crate) type SCREEN = c_void;
+
+pub(crate) const ERR: c_int = -1;
+
+#[allow(non_upper_case_globals)]
+extern "C" {
+    pub(crate) static mut stdscr: *mut WINDOW;
+    pub(crate) static COLOR_PAIRS: c_int;
+
+    pub(crate) fn newterm(term: *const c_char, outfd: *mut libc::FILE, infd: *mut libc::FILE) -> *mut SCREEN;
+    pub(crate) fn delscreen(sp: *mut SCREEN);
+    pub(crate) fn endwin() -> c_int;
+    pub(crate) fn start_color() -> c_int;
+    pub(crate) fn cbreak() -> c_int;
+    pub(crate) fn noecho() -> c_int;
+    pub(crate) fn nodelay(win: *mut WINDOW, bf: bool) -> c_int;
+    pub(crate) fn wtimeout(win: *mut WINDOW, delay: c_int);
+
+    pub(crate) fn wgetch(win: *mut WINDOW) -> c_int;
+    pub(crate) fn ungetch(ch: c_int) -> c_int;
+    pub(crate) fn wmove(win: *mut WINDOW, y: c_int, x: c_int) -> c_int;
+    pub(crate) fn waddnstr(win: *mut WINDOW, s: *const c_char, n: c_int) -> c_int;
+    pub(crate) fn wcolor_set(win: *mut WINDOW, pair: c_short, opts: *mut c_void) -> c_int;
+    pub(crate) fn wrefresh(win: *mut WINDOW) -> c_int;
+    pub(crate) fn init_pair(pair: c_short, f: c_short, b: c_short) -> c_int;
+    pub(crate) fn use_default_colors() -> c_int;
+    pub(crate) fn curs_set(visibility: c_int) -> c_int;
+    pub(crate) fn getmaxx(win: *mut WINDOW) -> c_int;
+    pub(crate) fn getmaxy(win: *mut WINDOW) -> c_int;
+    pub(crate) fn keypad(win: *mut WINDOW, bf: bool) -> c_int;
+    pub(crate) fn has_mouse() -> bool;
+    pub(crate) fn tigetnum(capname: *mut c_char) -> c_int;
+    pub(crate) fn tigetflag(capname: *mut c_char) -> c_int;
+    pub(crate) fn tigetstr(capname: *mut c_char) -> *mut c_char;
+}
+
+/// The file descriptor escape sequences are written to, next to ncurses.
+static OUTPUT_FD: AtomicI32 = AtomicI32::new(libc::STDOUT_FILENO);
+
+/// An ncurses screen on a terminal, set up the way the backend needs it.
+/// ncurses is shut down on drop.
+pub(crate) struct Session {
+    screen: *mut SCREEN,
+    input: *mut libc::FILE,
+    output: *mut libc::FILE,
+    _fds: TtyFds,
+}
+
+impl Session {
+    pub fn new(source: &TtySource) -> io::Result<Session> {
+        let fds = source.open()?;
+        let mode = |m: &[u8]| m.as_ptr() as *const c_char;
+        unsafe {
+            // ncurses owns the streams and closes them on shutdown, so it
+            // gets its own copies of the descriptors.
+            let input = open_stream(fds.input, mode(b"r\0"))?;
+            let output = match open_stream(fds.output, mode(b"w\0")) {
+                Ok(output) => output,
+                Err(err) => {
+                    libc::fclose(input);
+                    return Err(err);
+                }
+            };
+            let screen = newterm(std::ptr::null(), output, input);
+            if screen.is_null() {
+                libc::fclose(input);
+                libc::fclose(output);
+                return Err(io::Error::new(io::ErrorKind::Other, "ncurses could not set up the terminal"));
+            }
+            OUTPUT_FD.store(fds.output, Ordering::Relaxed);
+
+            start_color();
+            use_default_colors();
+            cbreak();
+            noecho();
+            curs_set(0);
+            nodelay(stdscr, true);
+            wtimeout(stdscr, 0);
+            keypad(stdscr, false);
+
+            Ok(Session {
+                screen,
+                input,
+                output,
+                _fds: fds,
+            })
+        }
+    }
+}
+
+/// Opens a stream on a copy of `fd`, closing the copy if that fails.
+unsafe fn open_stream(fd: c_int, mode: *const c_char) -> io::Result<*mut libc::FILE> {
+    let copy = libc::dup(fd);
+    if copy < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stream = libc::fdopen(copy, mode);
+    if stream.is_null() {
+        let err = io::Error::last_os_error();
+        libc::close(copy);
+        return Err(err);
+    }
+    Ok(stream)
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {
+            endwin();
+            delscreen(self.screen);
+            libc::fclose(self.input);
+            libc::fclose(self.output);
+        }
+        OUTPUT_FD.store(libc::STDOUT_FILENO, Ordering::Relaxed);
+    }
+}
+
+/// Returns true if a key press is waiting, without consuming it.
+pub(crate) fn input_pending() -> bool {
+    unsafe {
+        let c = wgetch(stdscr);
+        if c == ERR {
+            return false;
+        }
+        ungetch(c);
+        true
+    }
+}
+
+/// Reads all input that is immediately available. ncurses key codes are
+/// dropped, as the backend runs with `keypad` off and decodes escape
+/// sequences itself.
+pub(crate) fn read_input(buf: &mut Vec<u8>) {
+    loop {
+        let c = unsafe { wgetch(stdscr) };
+        if c == ERR {
+            break;
+        }
+        if c < 256 {
+            buf.push(c as u8);
+        }
+    }
+}
+
+/// The terminal of the current session, for the clipboard.
+pub(crate) struct Link;
+
+impl ClipboardLink for Link {
+    fn send(&mut self, bytes: &[u8]) {
+        send_bytes(bytes);
+    }
+
+    fn read_input(&mut self, buf: &mut Vec<u8>) {
+        read_input(buf);
+    }
+}
+
+/// Returns the size of the terminal in columns and rows.
+pub(crate) fn screen_size() -> (c_int, c_int) {
+    unsafe { (getmaxx(stdscr), getmaxy(stdscr)) }
+}
+
+/// Writes an escape sequence straight to the terminal, bypassing ncurses.
+pub(crate) fn send(seq: &str) {
+    send_bytes(seq.as_bytes());
+}
+
+fn send_bytes(mut bytes: &[u8]) {
+    let fd = OUTPUT_FD.load(Ordering::Relaxed);
+    while !bytes.is_empty() {
+        let n = unsafe { libc::write(fd, bytes.as_ptr() as *const c_void, bytes.len()) };
+        if n <= 0 {
+            if n < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            break;
+        }
+        bytes = &bytes[n as usize..];
+    }
+}
+
+/// Sends pending ncurses output to the terminal.
+pub(crate) fn refresh() {
+    unsafe {
+        wrefresh(stdscr);
+    }
+}
+
+/// Draws a rasterized screen with ncurses, only touching rows that changed
+/// since the previous frame. Nothing is shown until `refresh` is called.
+pub(crate) struct Painter {
+    prev: Vec<sys::ImTui::TCell>,
+    nx: i32,
+    ny: i32,
+    colors: ResolvedColors,
+    pairs: HashMap<(c_short, c_short), c_short>,
+    next_pair: c_int,
+    /// Set when a frame needed more pairs than the terminal has, so the next
+    /// frame starts the pairs over.
+    pairs_full: bool,
+    row: Vec<u8>,
+}
+
+impl Painter {
+    pub fn new() -> Painter {
+        Painter {
+            prev: vec![],
+            nx: 0,
+            ny: 0,
+            colors: ResolvedColors::default(),
+            pairs: HashMap::new(),
+            next_pair: 1,
+            pairs_full: false,
+            row: vec![],
+        }
+    }
+
+    /// Forces the next draw to repaint every row.
+    pub fn invalidate(&mut self) {
+        self.nx = 0;
+        self.ny = 0;
+    }
+
+    fn pair(&mut self, fg: Option<u8>, bg: Option<u8>) -> c_short {
+        let key = (
+            fg.map(|c| c as c_short).unwrap_or(-1),
+            bg.map(|c| c as c_short).unwrap_or(-1),
+        );
+        if let Some(pair) = self.pairs.get(&key) {
+            return *pair;
+        }
+        // Pair 0 is the terminal's default colors, and numbers have to fit a
+        // `c_short`.
+        let limit = unsafe { COLOR_PAIRS }.min(c_short::max_value() as c_int + 1);
+        if self.next_pair >= limit {
+            self.pairs_full = true;
+            return 0;
+        }
+
+        let pair = self.next_pair as c_short;
+        unsafe {
+            init_pair(pair, key.0, key.1);
+        }
+        self.next_pair += 1;
+        self.pairs.insert(key, pair);
+        pair
+    }
+
+    /// Draws the rows of `screen` that changed. Returns the number of cells
+    /// that changed.
+    pub fn draw(&mut self, screen: &sys::ImTui::TScreen, colors: &ResolvedColors) -> usize {
+        let cells = screen::cells(screen);
+        let (nx, ny) = (screen.nx, screen.ny);
+        let mut compare = nx == self.nx && ny == self.ny && *colors == self.colors;
+        if self.pairs_full {
+            // Redefining a pair recolors every cell that uses it, so the
+            // pairs only start over together with a full repaint.
+            self.pairs.clear();
+            self.next_pair = 1;
+            self.pairs_full = false;
+            compare = false;
+        }
+        if !compare {
+            self.prev.clear();
+            self.prev.resize(cells.len(), 0);
+            self.nx = nx;
+            self.ny = ny;
+            self.colors = *colors;
+        }
+
+        let mut changed = 0;
+        for y in 0..ny as usize {
+            let range = y * nx as usize..(y + 1) * nx as usize;
+            if compare && cells[range.clone()] == self.prev[range.clone()] {
+                continue;
+            }
+            changed += if compare {
+                cells[range.clone()].iter().zip(&self.prev[range.clone()]).filter(|(a, b)| a != b).count()
+            } else {
+                range.len()
+            };
+
+            let mut last_pair = None;
+            unsafe {
+                wmove(stdscr, y as c_int, 0);
+            }
+            for &raw in &cells[range.clone()] {
+                let cell = Cell::from(raw);
+                let (fg, bg) = colors.apply(cell);
+                let pair = self.pair(fg, bg);
+                if last_pair != Some(pair) {
+                    self.flush_row();
+                    unsafe {
+                        wcolor_set(stdscr, pair, std::ptr::null_mut());
+                    }
+                    last_pair = Some(pair);
+                }
+                self.row.push(match cell.ch {
+                    0 => b' ',
+                    c => c as u8,
+                });
+            }
+            self.flush_row();
+            self.prev[range.clone()].copy_from_slice(&cells[range]);
+        }
+        changed
+    }
+
+    fn flush_row(&mut self) {
+        if !self.row.is_empty() {
+            unsafe {
+                waddnstr(stdscr, self.row.as_ptr() as *const c_char, self.row.len() as c_int);
+            }
+            self.row.clear();
+        }
+    }
+}
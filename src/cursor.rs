@@ -0,0 +1,182 @@
+use crate::curses;
+use imgui;
+use std::cell::Cell;
+use std::os::raw::{c_int, c_void};
+
+/// Shape of the terminal's hardware cursor, set with DECSCUSR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorShape {
+    /// Whatever the user configured in their terminal.
+    Default,
+    BlinkingBlock,
+    SteadyBlock,
+    BlinkingUnderline,
+    SteadyUnderline,
+    BlinkingBar,
+    SteadyBar,
+}
+
+impl Default for CursorShape {
+    fn default() -> CursorShape {
+        CursorShape::Default
+    }
+}
+
+impl CursorShape {
+    pub(crate) fn decscusr(self) -> u8 {
+        match self {
+            CursorShape::Default => 0,
+            CursorShape::BlinkingBlock => 1,
+            CursorShape::SteadyBlock => 2,
+            CursorShape::BlinkingUnderline => 3,
+            CursorShape::SteadyUnderline => 4,
+            CursorShape::BlinkingBar => 5,
+            CursorShape::SteadyBar => 6,
+        }
+    }
+}
+
+/// Tracks the text input caret of one imgui context.
+///
+/// imgui only reports the caret through the IME callback, which takes no
+/// user data. The hook keeps a pointer to the position in the context's
+/// `ImeWindowHandle`, which only imgui's own Windows IME callback reads.
+pub(crate) struct CaretHook {
+    position: Box<Cell<(c_int, c_int)>>,
+}
+
+unsafe extern "C" fn set_caret(x: c_int, y: c_int) {
+    let position = (*imgui::sys::igGetIO()).ImeWindowHandle as *const Cell<(c_int, c_int)>;
+    if !position.is_null() {
+        (*position).set((x, y));
+    }
+}
+
+impl CaretHook {
+    /// Hooks the IME callback of the current imgui context.
+    pub fn install() -> CaretHook {
+        let hook = CaretHook {
+            position: Box::new(Cell::new((-1, -1))),
+        };
+        unsafe {
+            let io = &mut *imgui::sys::igGetIO();
+            io.ImeSetInputScreenPosFn = Some(set_caret);
+            io.ImeWindowHandle = hook.pointer();
+        }
+        hook
+    }
+
+    fn pointer(&self) -> *mut c_void {
+        &*self.position as *const Cell<(c_int, c_int)> as *mut c_void
+    }
+
+    /// Returns the cell the cursor should be in, if a text input is active.
+    ///
+    /// imgui reports the caret one unit to the left of where it is drawn, so
+    /// that IME windows don't cover it.
+    pub fn caret(&self) -> Option<(c_int, c_int)> {
+        let want_text_input = unsafe { (*imgui::sys::igGetIO()).WantTextInput };
+        let (x, y) = self.position.get();
+        if want_text_input && y >= 0 {
+            Some((x + 1, y))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for CaretHook {
+    fn drop(&mut self) {
+        unsafe {
+            if imgui::sys::igGetCurrentContext().is_null() {
+                return;
+            }
+            let io = &mut *imgui::sys::igGetIO();
+            if io.ImeWindowHandle == self.pointer() {
+                io.ImeSetInputScreenPosFn = None;
+                io.ImeWindowHandle = std::ptr::null_mut();
+            }
+        }
+    }
+}
+
+/// Keeps the ncurses cursor on the caret of the active text input, and
+/// hidden while no text input is active.
+pub(crate) struct Cursor {
+    applied_shape: Option<CursorShape>,
+    visible: bool,
+}
+
+impl Cursor {
+    pub fn new() -> Cursor {
+        Cursor {
+            applied_shape: None,
+            visible: false,
+        }
+    }
+
+    /// Moves the ncurses cursor to `caret`, from `Frontend::caret`. Call
+    /// before refreshing the screen.
+    pub fn update(&mut self, caret: Option<(c_int, c_int)>) {
+        match caret {
+            Some((x, y)) => unsafe {
+                curses::wmove(curses::stdscr, y, x);
+                if !self.visible {
+                    curses::curs_set(1);
+                    self.visible = true;
+                }
+            },
+            None => {
+                if self.visible {
+                    unsafe {
+                        curses::curs_set(0);
+                    }
+                    self.visible = false;
+                }
+            }
+        }
+    }
+
+    /// Sends the cursor shape if it changed. Call after refreshing the screen,
+    /// so the escape sequence isn't interleaved with ncurses output.
+    pub fn apply_shape(&mut self, shape: CursorShape) {
+        if self.applied_shape != Some(shape) {
+            write_shape(shape);
+            self.applied_shape = Some(shape);
+        }
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        if self.applied_shape.is_some() {
+            write_shape(CursorShape::Default);
+        }
+    }
+}
+
+fn write_shape(shape: CursorShape) {
+    curses::send(&format!("\x1b[{} q", shape.decscusr()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_shapes_to_decscusr_codes() {
+        let shapes = [
+            (CursorShape::Default, 0),
+            (CursorShape::BlinkingBlock, 1),
+            (CursorShape::SteadyBlock, 2),
+            (CursorShape::BlinkingUnderline, 3),
+            (CursorShape::SteadyUnderline, 4),
+            (CursorShape::BlinkingBar, 5),
+            (CursorShape::SteadyBar, 6),
+        ];
+        for &(shape, code) in &shapes {
+            assert_eq!(shape.decscusr(), code, "{:?}", shape);
+        }
+        assert_eq!(CursorShape::default(), CursorShape::Default);
+    }
+}
@@ -0,0 +1,284 @@
+//! Saving the rendered screen as ANSI text, HTML or SVG.
+
+use crate::ansi;
+use crate::color::{palette_rgb, ResolvedColors};
+use crate::screen::{self, Cell};
+use crate::sys;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Width and height of a cell in an SVG export, for a 14px monospace font.
+const SVG_CELL_WIDTH: f32 = 8.4;
+const SVG_CELL_HEIGHT: f32 = 17.0;
+const SVG_FONT_SIZE: f32 = 14.0;
+
+/// A copy of a rendered screen, taken with `screenshot()` on a backend.
+///
+/// Cells only carry a character and two palette colors, so that is all an
+/// export preserves. HTML and SVG show palette colors with the xterm
+/// defaults; ANSI text uses the terminal's own palette, and its default
+/// colors where the backend's `DefaultColors` ask for them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Screenshot {
+    pub width: u16,
+    pub height: u16,
+    /// The cells in row-major order.
+    pub cells: Vec<Cell>,
+    colors: ResolvedColors,
+}
+
+impl Screenshot {
+    pub(crate) fn new(screen: &sys::ImTui::TScreen, colors: ResolvedColors) -> Screenshot {
+        Screenshot {
+            width: screen.nx.max(0) as u16,
+            height: screen.ny.max(0) as u16,
+            cells: screen::cells(screen).iter().map(|&cell| Cell::from(cell)).collect(),
+            colors,
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks(self.width.max(1) as usize)
+    }
+
+    /// The characters on screen, one line per row, without trailing spaces.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.rows() {
+            let line: String = row.iter().map(|cell| cell.to_char()).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The screen as lines of text colored with SGR sequences, for `cat` or
+    /// `less -R`.
+    pub fn to_ansi(&self) -> String {
+        let mut out = vec![];
+        for row in self.rows() {
+            let mut last = None;
+            for &cell in row {
+                let colors = self.colors.apply(cell);
+                if last != Some(colors) {
+                    ansi::write_sgr(&mut out, colors.0, colors.1);
+                    last = Some(colors);
+                }
+                let mut utf8 = [0u8; 4];
+                out.extend_from_slice(cell.to_char().encode_utf8(&mut utf8).as_bytes());
+            }
+            out.extend_from_slice(b"\x1b[0m\n");
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    /// A standalone HTML page with the screen in a `<pre>`, colored with
+    /// inline styles.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n<body>\n\
+             <pre style=\"font-family: monospace; line-height: 1.2; display: inline-block; margin: 0\">",
+        );
+        for row in self.rows() {
+            for (cell, len) in runs(row) {
+                let text: String = row[cell..cell + len].iter().map(|cell| cell.to_char()).collect();
+                let _ = write!(
+                    html,
+                    "<span style=\"color: {}; background-color: {}\">",
+                    css_color(row[cell].fg),
+                    css_color(row[cell].bg)
+                );
+                push_escaped(&mut html, &text);
+                html.push_str("</span>");
+            }
+            html.push('\n');
+        }
+        html.push_str("</pre>\n</body>\n</html>\n");
+        html
+    }
+
+    /// An SVG image of the screen on a monospace grid.
+    pub fn to_svg(&self) -> String {
+        let width = self.width as f32 * SVG_CELL_WIDTH;
+        let height = self.height as f32 * SVG_CELL_HEIGHT;
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" \
+             font-family=\"monospace\" font-size=\"{}\">\n",
+            width, height, width, height, SVG_FONT_SIZE
+        );
+        for (y, row) in self.rows().enumerate() {
+            let top = y as f32 * SVG_CELL_HEIGHT;
+            for (cell, len) in runs(row) {
+                let left = cell as f32 * SVG_CELL_WIDTH;
+                let run_width = len as f32 * SVG_CELL_WIDTH;
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    left, top, run_width, SVG_CELL_HEIGHT, css_color(row[cell].bg)
+                );
+                let text: String = row[cell..cell + len].iter().map(|cell| cell.to_char()).collect();
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let _ = write!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" fill=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" \
+                     xml:space=\"preserve\">",
+                    left,
+                    top + SVG_CELL_HEIGHT * 0.8,
+                    css_color(row[cell].fg),
+                    run_width
+                );
+                push_escaped(&mut svg, &text);
+                svg.push_str("</text>\n");
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Writes the screen to `path`, as SVG or HTML if the extension is
+    /// `svg`, `html` or `htm`, and as ANSI text otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        let contents = match extension.as_str() {
+            "svg" => self.to_svg(),
+            "html" | "htm" => self.to_html(),
+            _ => self.to_ansi(),
+        };
+        fs::write(path, contents)
+    }
+}
+
+/// Splits a row into runs of cells with the same colors, as the index of the
+/// first cell and the length.
+fn runs(row: &[Cell]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for (i, cell) in row.iter().enumerate() {
+        match runs.last_mut() {
+            Some((start, len)) if row[*start].fg == cell.fg && row[*start].bg == cell.bg => *len += 1,
+            _ => runs.push((i, 1)),
+        }
+    }
+    runs
+}
+
+fn css_color(index: u8) -> String {
+    let [r, g, b] = palette_rgb(index);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Appends `text` with the characters that are special in HTML and XML
+/// escaped.
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(c: char, fg: u8, bg: u8) -> Cell {
+        Cell { ch: c as u16, fg, bg }
+    }
+
+    fn screenshot(width: u16, cells: Vec<Cell>) -> Screenshot {
+        Screenshot {
+            width,
+            height: (cells.len() / width as usize) as u16,
+            cells,
+            colors: ResolvedColors::default(),
+        }
+    }
+
+    /// "a<" in white on black, over "&" in red on black and a quote in red
+    /// on blue.
+    fn sample() -> Screenshot {
+        screenshot(2, vec![cell('a', 15, 0), cell('<', 15, 0), cell('&', 9, 0), cell('"', 9, 21)])
+    }
+
+    #[test]
+    fn escapes_html_and_xml() {
+        let mut out = String::new();
+        push_escaped(&mut out, "<a href=\"x\">&</a>");
+        assert_eq!(out, "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn exports_text() {
+        let cells = vec![
+            cell('a', 15, 0),
+            Cell::default(),
+            Cell::default(),
+            cell('b', 15, 0),
+            cell('c', 15, 0),
+            cell(' ', 15, 0),
+        ];
+        let screenshot = screenshot(3, cells);
+        assert_eq!(screenshot.to_text(), "a\nbc\n");
+    }
+
+    #[test]
+    fn exports_ansi() {
+        assert_eq!(
+            sample().to_ansi(),
+            "\x1b[38;5;15m\x1b[48;5;0ma<\x1b[0m\n\
+             \x1b[38;5;9m\x1b[48;5;0m&\x1b[38;5;9m\x1b[48;5;21m\"\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn exports_default_colors_to_ansi() {
+        let mut screenshot = sample();
+        screenshot.colors = ResolvedColors {
+            foreground: Some(15),
+            background: Some(0),
+        };
+        assert!(screenshot.to_ansi().starts_with("\x1b[39m\x1b[49ma<\x1b[0m\n"));
+    }
+
+    #[test]
+    fn exports_html() {
+        let html = sample().to_html();
+        let body = "<pre style=\"font-family: monospace; line-height: 1.2; display: inline-block; margin: 0\">\
+                    <span style=\"color: #ffffff; background-color: #000000\">a&lt;</span>\n\
+                    <span style=\"color: #ff0000; background-color: #000000\">&amp;</span>\
+                    <span style=\"color: #ff0000; background-color: #0000ff\">&quot;</span>\n\
+                    </pre>\n</body>\n</html>\n";
+        assert!(html.starts_with("<!DOCTYPE html>\n"), "{}", html);
+        assert!(html.ends_with(body), "{}", html);
+    }
+
+    #[test]
+    fn exports_svg() {
+        let svg = screenshot(2, vec![cell('a', 15, 0), cell('<', 15, 0)]).to_svg();
+        assert_eq!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"16.8\" height=\"17\" viewBox=\"0 0 16.8 17\" \
+             font-family=\"monospace\" font-size=\"14\">\n\
+             <rect x=\"0\" y=\"0\" width=\"16.8\" height=\"17\" fill=\"#000000\"/>\n\
+             <text x=\"0\" y=\"13.6\" fill=\"#ffffff\" textLength=\"16.8\" lengthAdjust=\"spacingAndGlyphs\" \
+             xml:space=\"preserve\">a&lt;</text>\n\
+             </svg>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_in_svg_text() {
+        let svg = screenshot(3, vec![cell('"', 15, 0), cell('&', 15, 0), cell('>', 15, 0)]).to_svg();
+        assert!(svg.contains(">&quot;&amp;&gt;</text>"), "{}", svg);
+    }
+}
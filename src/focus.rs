@@ -0,0 +1,262 @@
+//! Moving the keyboard focus between windows.
+
+use crate::input::{Event, Key, KeyEvent, Modifiers, MouseButton, MouseKind};
+use crate::layout::Rect;
+use imgui;
+
+/// A window in a `FocusRing`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FocusEntry {
+    /// The window's imgui name, i.e. its title including any `##` suffix.
+    pub name: imgui::ImString,
+    /// Where the window is on screen, so clicking it moves the focus there.
+    pub rect: Option<Rect>,
+}
+
+/// The windows that can have the focus, in the order Tab visits them, and
+/// which of them has it.
+///
+/// Each frame, the app lists its windows with `set_entries`, passes the
+/// frame's events to `handle_events`, and calls `apply` once the imgui
+/// frame has started, which gives the window the focus in imgui too. While
+/// drawing, `is_active` tells each window whether to show itself as
+/// focused.
+///
+/// Tab and Shift+Tab cycle through the windows, keys added with `bind` jump
+/// to one, and a left click inside an entry's rect focuses it.
+///
+/// Keys typed into a text field aren't meant for the ring: while imgui
+/// wanted text input as of the last `apply`, `handle_events` ignores keys
+/// and only clicks move the focus.
+#[derive(Clone, Debug)]
+pub struct FocusRing {
+    entries: Vec<FocusEntry>,
+    focused: usize,
+    /// Set when the focus moved and imgui hasn't been told yet.
+    pending: bool,
+    jumps: Vec<(KeyEvent, usize)>,
+    /// Whether a text field had the keyboard in the last frame.
+    typing: bool,
+    /// Whether Tab and Shift+Tab move the focus. Turn it off for windows
+    /// that use Tab themselves.
+    pub cycle_keys: bool,
+}
+
+impl Default for FocusRing {
+    fn default() -> FocusRing {
+        FocusRing {
+            entries: vec![],
+            focused: 0,
+            pending: false,
+            jumps: vec![],
+            typing: false,
+            cycle_keys: true,
+        }
+    }
+}
+
+impl FocusRing {
+    pub fn new() -> FocusRing {
+        FocusRing::default()
+    }
+
+    /// Binds the digit keys from `first` to entries 0, 1, 2 and so on,
+    /// e.g. `'1'` for windows numbered from one.
+    pub fn with_digit_jumps(mut self, first: char) -> FocusRing {
+        let first = first.to_digit(10).unwrap_or(0);
+        for (index, digit) in (first..10).enumerate() {
+            let key = Key::Char(std::char::from_digit(digit, 10).unwrap());
+            self.bind(KeyEvent { key, modifiers: Modifiers::default() }, index);
+        }
+        self
+    }
+
+    /// Makes `key` focus the entry at `index`.
+    pub fn bind(&mut self, key: KeyEvent, index: usize) {
+        self.jumps.retain(|(bound, _)| *bound != key);
+        self.jumps.push((key, index));
+    }
+
+    /// Replaces the windows in the ring. The focus stays at the same index,
+    /// or moves to the last window if there are fewer.
+    pub fn set_entries(&mut self, entries: Vec<FocusEntry>) {
+        self.entries = entries;
+        if self.focused >= self.entries.len() && !self.entries.is_empty() {
+            self.focus(self.entries.len() - 1);
+        }
+    }
+
+    pub fn entries(&self) -> &[FocusEntry] {
+        &self.entries
+    }
+
+    /// The index of the focused window.
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn is_active(&self, index: usize) -> bool {
+        index == self.focused
+    }
+
+    pub fn focus(&mut self, index: usize) {
+        if index != self.focused {
+            self.focused = index;
+            self.pending = true;
+        }
+    }
+
+    pub fn focus_next(&mut self) {
+        if !self.entries.is_empty() {
+            self.focus((self.focused + 1) % self.entries.len());
+        }
+    }
+
+    pub fn focus_previous(&mut self) {
+        if !self.entries.is_empty() {
+            self.focus((self.focused + self.entries.len() - 1) % self.entries.len());
+        }
+    }
+
+    /// Moves the focus for Tab, Shift+Tab, bound keys and clicks. Returns
+    /// true if it moved.
+    pub fn handle_events(&mut self, events: &[Event]) -> bool {
+        let before = self.focused;
+        for event in events {
+            match event {
+                Event::Key(_) if self.typing => {}
+                Event::Key(key) => {
+                    if self.cycle_keys && key.key == Key::Tab && !key.modifiers.shift {
+                        self.focus_next();
+                    } else if self.cycle_keys && (key.key == Key::BackTab || key.key == Key::Tab) {
+                        self.focus_previous();
+                    } else if let Some(&(_, index)) = self.jumps.iter().find(|(bound, _)| bound == key) {
+                        if index < self.entries.len() {
+                            self.focus(index);
+                        }
+                    }
+                }
+                Event::Mouse(mouse) if mouse.kind == MouseKind::Press(MouseButton::Left) => {
+                    let clicked = self.entries.iter().position(|entry| match entry.rect {
+                        Some(rect) => rect.contains(mouse.x, mouse.y),
+                        None => false,
+                    });
+                    if let Some(index) = clicked {
+                        // imgui focuses the clicked window by itself.
+                        self.focused = index;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.focused != before
+    }
+
+    /// Gives the focused window the focus in imgui, if it moved since the
+    /// last call. Call it in the frame, before drawing the windows.
+    pub fn apply(&mut self, ui: &imgui::Ui) {
+        self.typing = ui.io().want_text_input;
+        if !self.pending {
+            return;
+        }
+        self.pending = false;
+        if let Some(entry) = self.entries.get(self.focused) {
+            unsafe {
+                imgui::sys::igSetWindowFocusStr(entry.name.as_ptr());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::MouseEvent;
+
+    fn ring(len: usize) -> FocusRing {
+        let mut ring = FocusRing::new();
+        ring.set_entries(
+            (0..len)
+                .map(|i| FocusEntry {
+                    name: imgui::ImString::new(format!("window {}", i)),
+                    rect: None,
+                })
+                .collect(),
+        );
+        ring
+    }
+
+    fn key(key: Key, shift: bool) -> Event {
+        Event::Key(KeyEvent {
+            key,
+            modifiers: Modifiers { shift, ..Modifiers::default() },
+        })
+    }
+
+    #[test]
+    fn tab_wraps_around() {
+        let mut ring = ring(3);
+        assert!(ring.handle_events(&[key(Key::Tab, false), key(Key::Tab, false)]));
+        assert_eq!(ring.focused(), 2);
+        assert!(ring.handle_events(&[key(Key::Tab, false)]));
+        assert_eq!(ring.focused(), 0);
+    }
+
+    #[test]
+    fn shift_tab_wraps_around() {
+        let mut ring = ring(3);
+        assert!(ring.handle_events(&[key(Key::BackTab, false)]));
+        assert_eq!(ring.focused(), 2);
+        assert!(ring.handle_events(&[key(Key::Tab, true)]));
+        assert_eq!(ring.focused(), 1);
+        ring.handle_events(&[key(Key::Tab, true), key(Key::Tab, true)]);
+        assert_eq!(ring.focused(), 2);
+    }
+
+    #[test]
+    fn cycle_keys_can_be_turned_off() {
+        let mut ring = ring(3);
+        ring.cycle_keys = false;
+        assert!(!ring.handle_events(&[key(Key::Tab, false), key(Key::BackTab, false)]));
+        assert_eq!(ring.focused(), 0);
+    }
+
+    #[test]
+    fn leaves_keys_to_text_fields() {
+        let mut ring = ring(3).with_digit_jumps('1');
+        ring.entries[2].rect = Some(Rect::new(0, 0, 10, 5));
+        ring.typing = true;
+        let digit = Event::Key(KeyEvent {
+            key: Key::Char('2'),
+            modifiers: Modifiers::default(),
+        });
+        assert!(!ring.handle_events(&[
+            digit.clone(),
+            key(Key::Tab, false),
+            key(Key::BackTab, false)
+        ]));
+        assert_eq!(ring.focused(), 0);
+
+        // Clicks still move the focus.
+        let click = Event::Mouse(MouseEvent {
+            kind: MouseKind::Press(MouseButton::Left),
+            x: 3,
+            y: 2,
+            modifiers: Modifiers::default(),
+            clicks: 1,
+        });
+        assert!(ring.handle_events(&[click]));
+        assert_eq!(ring.focused(), 2);
+
+        ring.typing = false;
+        assert!(ring.handle_events(&[digit]));
+        assert_eq!(ring.focused(), 1);
+    }
+
+    #[test]
+    fn a_single_window_keeps_the_focus() {
+        let mut ring = ring(1);
+        assert!(!ring.handle_events(&[key(Key::Tab, false), key(Key::Tab, true)]));
+        assert_eq!(ring.focused(), 0);
+    }
+}
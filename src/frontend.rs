@@ -0,0 +1,316 @@
+//! The part of every backend that doesn't depend on how the terminal is
+//! reached.
+
+use crate::clipboard::{Clipboard, ClipboardLink, ClipboardProvider};
+use crate::color::{DefaultColors, ResolvedColors};
+use crate::cursor::{CaretHook, CursorShape};
+use crate::export::Screenshot;
+use crate::imgui_io::ImGuiInput;
+use crate::input::{Event, Parser};
+use crate::overlay::DebugOverlay;
+use crate::record::Recorder;
+use crate::replay::{InputCapture, InputReplay};
+use crate::screen::Screen;
+use crate::stats::FrameTimer;
+use crate::sys;
+use crate::vsync::{Activity, VSync};
+use imgui;
+use imgui::internal::RawCast;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many frontends use each imgui context, by the context's address. The
+/// text renderer is set up for a context with its first frontend and shut
+/// down with its last.
+static TEXT_RENDERER_USERS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+/// Everything a backend does besides moving bytes and painting: decoding
+/// input, feeding imgui, pacing frames, rasterizing the screen, and the
+/// recording, replay and debugging tools.
+///
+/// The frontend draws into a viewport of the terminal whose top row is given
+/// as the origin, counting from 0.
+pub struct Frontend {
+    screen: Screen,
+    vsync: VSync,
+    pub(crate) activity: Activity,
+    pub(crate) stats: FrameTimer,
+    pub(crate) overlay: DebugOverlay,
+    parser: Parser,
+    input: ImGuiInput,
+    events: Vec<Event>,
+    default_colors: DefaultColors,
+    /// The palette indices that stand for the default colors in `screen`.
+    colors: ResolvedColors,
+    pub(crate) cursor_shape: CursorShape,
+    recorder: Option<Recorder>,
+    capture: Option<InputCapture>,
+    replay: Option<InputReplay>,
+    /// Decodes the replayed input, on a clock that follows the captured
+    /// frame times, so a replay doesn't depend on how fast it runs.
+    replay_parser: Parser,
+    replay_clock: Instant,
+    /// The raw input of the current frame, for the capture.
+    frame_input: Vec<u8>,
+    clipboard: Box<Clipboard>,
+    caret: CaretHook,
+    /// The address of the imgui context the frontend was made for.
+    context: usize,
+}
+
+impl Frontend {
+    /// Sets up the current imgui context for rendering to text.
+    pub(crate) fn new(active_fps: f32, idle_fps: f32) -> Frontend {
+        let context = unsafe { imgui::sys::igGetCurrentContext() } as usize;
+        {
+            let mut users = TEXT_RENDERER_USERS.lock().unwrap();
+            match users.iter_mut().find(|(c, _)| *c == context) {
+                Some((_, count)) => *count += 1,
+                None => {
+                    users.push((context, 1));
+                    unsafe {
+                        sys::ImTui_ImplText_Init();
+                    }
+                }
+            }
+        }
+        let mut clipboard = Clipboard::new();
+        clipboard.install();
+        Frontend {
+            screen: Screen::new(),
+            vsync: VSync::new(active_fps, idle_fps),
+            activity: Activity::new(),
+            stats: FrameTimer::new(),
+            overlay: DebugOverlay::default(),
+            parser: Parser::new(),
+            input: ImGuiInput::new(),
+            events: vec![],
+            default_colors: DefaultColors::default(),
+            colors: ResolvedColors::default(),
+            cursor_shape: CursorShape::default(),
+            recorder: None,
+            capture: None,
+            replay: None,
+            replay_parser: Parser::new(),
+            replay_clock: Instant::now(),
+            frame_input: vec![],
+            clipboard,
+            caret: CaretHook::install(),
+            context,
+        }
+    }
+
+    pub(crate) fn set_clipboard_provider(&mut self, provider: Option<Box<dyn ClipboardProvider>>) {
+        self.clipboard.set_provider(provider);
+    }
+
+    pub(crate) fn set_osc52_clipboard(&mut self, write: bool, read: bool) {
+        self.clipboard.set_osc52(write, read);
+    }
+
+    /// Lets the clipboard reach the terminal directly, which OSC 52 reads
+    /// need. `supports_read` is whether the terminal says it answers them.
+    pub(crate) fn set_clipboard_link(&mut self, link: Box<dyn ClipboardLink>, supports_read: bool) {
+        self.clipboard.set_link(Some(link));
+        self.clipboard.detected_osc52(supports_read);
+    }
+
+    /// Moves the OSC 52 copies made during the frame to `out`, for backends
+    /// without a clipboard link to write with the frame.
+    pub(crate) fn take_clipboard_output(&mut self, out: &mut Vec<u8>) {
+        self.clipboard.take_output(out);
+    }
+
+    /// Moves the input that arrived while the clipboard waited for the
+    /// terminal to `buf`, to pass it to the next `new_frame`.
+    pub(crate) fn take_stashed_input(&mut self, buf: &mut Vec<u8>) {
+        self.clipboard.take_stashed_input(buf);
+    }
+
+    pub(crate) fn default_colors(&self) -> DefaultColors {
+        self.default_colors
+    }
+
+    pub(crate) fn set_default_colors(&mut self, default_colors: DefaultColors) {
+        self.default_colors = default_colors;
+    }
+
+    pub(crate) fn set_recorder(&mut self, recorder: Option<Recorder>) -> Option<Recorder> {
+        std::mem::replace(&mut self.recorder, recorder)
+    }
+
+    pub(crate) fn set_input_capture(&mut self, capture: Option<InputCapture>) -> Option<InputCapture> {
+        std::mem::replace(&mut self.capture, capture)
+    }
+
+    pub(crate) fn set_input_replay(&mut self, replay: Option<InputReplay>) -> Option<InputReplay> {
+        self.replay_parser = Parser::new();
+        self.replay_clock = Instant::now();
+        std::mem::replace(&mut self.replay, replay)
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replay.as_ref().map_or(false, |replay| replay.remaining() > 0)
+    }
+
+    /// Records input that is passed on as events rather than to `new_frame`.
+    /// Live input is ignored while replaying.
+    pub(crate) fn record_input(&mut self, input: &[u8]) {
+        if self.is_replaying() {
+            return;
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.input(input);
+        }
+        if self.capture.is_some() {
+            self.frame_input.extend_from_slice(input);
+        }
+    }
+
+    /// Decodes `input` and starts a new imgui frame for a viewport of the
+    /// given size.
+    ///
+    /// While replaying, the input, size and timing come from the next
+    /// captured frame instead.
+    pub(crate) fn new_frame(&mut self, input: &[u8], size: (u16, u16), origin: u16) {
+        if self.is_replaying() {
+            self.replay_frame();
+            return;
+        }
+        let mut events = std::mem::replace(&mut self.events, vec![]);
+        events.clear();
+        self.record_input(input);
+        let now = Instant::now();
+        self.parser.feed(input, now, &mut events);
+        self.parser.flush(now, &mut events);
+        self.start_frame(events, !input.is_empty(), size, origin, None);
+    }
+
+    /// Like `new_frame`, for input that was already decoded.
+    pub(crate) fn new_frame_with_events(&mut self, events: Vec<Event>, had_input: bool, size: (u16, u16), origin: u16) {
+        if self.is_replaying() {
+            self.replay_frame();
+            return;
+        }
+        self.start_frame(events, had_input, size, origin, None);
+    }
+
+    fn replay_frame(&mut self) {
+        let frame = match self.replay.as_mut().and_then(|replay| replay.next_frame()) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let mut events = std::mem::replace(&mut self.events, vec![]);
+        events.clear();
+        self.replay_clock += frame.delta;
+        self.replay_parser.feed(&frame.input, self.replay_clock, &mut events);
+        self.replay_parser.flush(self.replay_clock, &mut events);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.input(&frame.input);
+        }
+        self.frame_input = frame.input;
+        let had_input = !self.frame_input.is_empty();
+        self.start_frame(events, had_input, frame.size, frame.origin, Some(frame.delta));
+    }
+
+    fn start_frame(&mut self, events: Vec<Event>, had_input: bool, size: (u16, u16), origin: u16,
+                   delta: Option<Duration>) {
+        self.events = events;
+        self.activity.update(had_input, &self.events);
+
+        // Mouse reports use terminal rows. Only those over the viewport are
+        // kept, moved to viewport rows.
+        let (nx, ny) = size;
+        self.events.retain(|event| match event {
+            Event::Mouse(mouse) => mouse.y >= origin && mouse.y < origin + ny,
+            _ => true,
+        });
+        for event in self.events.iter_mut() {
+            if let Event::Mouse(mouse) = event {
+                mouse.y -= origin;
+            }
+        }
+
+        let delta = self.input.new_frame([nx as f32, ny as f32], &mut self.events, delta);
+        if self.overlay.visible {
+            self.overlay.record_events(&self.events);
+        }
+        if let Some(capture) = self.capture.as_mut() {
+            capture.frame(delta, size, origin, &self.frame_input);
+        }
+        self.frame_input.clear();
+        unsafe {
+            sys::ImTui_ImplText_NewFrame();
+        }
+        self.stats.end_input();
+    }
+
+    pub(crate) fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Rasterizes `draw_data` into `screen()` and waits for the next frame.
+    /// The backend then paints the screen, and ends the frame with
+    /// `stats.end_frame()` once the output is written.
+    pub(crate) fn rasterize<F: FnMut() -> bool>(&mut self, draw_data: &imgui::DrawData, mut input_pending: F) {
+        self.stats.begin_render(draw_data);
+        let [nx, ny] = draw_data.display_size;
+        self.colors = self.default_colors.mark(draw_data);
+        unsafe {
+            let raw_ptr = draw_data.raw() as *const imgui::sys::ImDrawData as *mut imgui::sys::ImDrawData;
+            sys::ImTui_ImplText_RenderDrawData(raw_ptr, self.screen.prepare(nx as i32, ny as i32));
+        }
+        self.stats.rasterized();
+        let (sx, sy) = (self.screen.raw().nx as usize, self.screen.raw().ny as usize);
+        self.overlay.draw(draw_data, self.screen.cells_mut(), sx, sy, &self.stats.history);
+        // An incomplete escape sequence becomes key presses after a timeout,
+        // which needs a frame even without more input.
+        let escape = self.parser.escape_deadline();
+        self.vsync.wait(self.activity.is_active(), || {
+            input_pending() || escape.map_or(false, |deadline| Instant::now() >= deadline)
+        });
+        self.stats.waited();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.frame(self.screen.raw(), &self.colors, self.caret.caret(), self.cursor_shape);
+        }
+    }
+
+    /// The screen rasterized by the last call to `rasterize`.
+    pub(crate) fn screen(&self) -> &sys::ImTui::TScreen {
+        self.screen.raw()
+    }
+
+    /// The cell of the active text input's caret, where the terminal cursor
+    /// goes.
+    pub(crate) fn caret(&self) -> Option<(i32, i32)> {
+        self.caret.caret()
+    }
+
+    /// How the default colors appear in `screen()`, for painting it.
+    pub(crate) fn colors(&self) -> ResolvedColors {
+        self.colors
+    }
+
+    pub(crate) fn screenshot(&self) -> Screenshot {
+        Screenshot::new(self.screen.raw(), self.colors)
+    }
+}
+
+impl Drop for Frontend {
+    fn drop(&mut self) {
+        self.clipboard.uninstall();
+        let mut users = TEXT_RENDERER_USERS.lock().unwrap();
+        if let Some(index) = users.iter().position(|(c, _)| *c == self.context) {
+            users[index].1 -= 1;
+            if users[index].1 == 0 {
+                users.swap_remove(index);
+                // Nothing is left to shut down once the context is gone.
+                if unsafe { imgui::sys::igGetCurrentContext() } as usize == self.context {
+                    unsafe {
+                        sys::ImTui_ImplText_Shutdown();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Rendering into memory, without a terminal.
+
+use crate::backend::Backend;
+use crate::frontend::Frontend;
+use imgui;
+
+/// A backend without a terminal, which renders into memory and never waits
+/// between frames.
+///
+/// Input is passed to `new_frame` as the bytes a terminal would send, or
+/// comes from an `InputReplay`, so a captured session can be replayed and
+/// its screens checked in a test.
+pub struct Headless {
+    frontend: Frontend,
+    size: (u16, u16),
+}
+
+impl Headless {
+    pub fn new(size: (u16, u16)) -> Headless {
+        Headless {
+            frontend: Frontend::new(0.0, 0.0),
+            size: (size.0.max(1), size.1.max(1)),
+        }
+    }
+
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.size = (columns.max(1), rows.max(1));
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Feeds `input`, raw terminal input such as `b"\x1b[A"`, to imgui and
+    /// starts a new frame.
+    pub fn new_frame(&mut self, input: &[u8]) {
+        self.frontend.stats.begin_input();
+        self.frontend.new_frame(input, self.size, 0);
+    }
+
+    pub fn render(&mut self, draw_data: &imgui::DrawData) {
+        self.frontend.rasterize(draw_data, || true);
+        // There is no terminal to copy to.
+        self.frontend.take_clipboard_output(&mut vec![]);
+        self.frontend.stats.end_frame();
+    }
+
+    /// The text of the last rendered frame, one line per row, without
+    /// trailing spaces.
+    pub fn text(&self) -> String {
+        self.screenshot().to_text()
+    }
+}
+
+impl Backend for Headless {
+    fn frontend(&self) -> &Frontend {
+        &self.frontend
+    }
+
+    fn frontend_mut(&mut self) -> &mut Frontend {
+        &mut self.frontend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Event, Key, KeyEvent};
+    use crate::replay::InputReplay;
+    use crate::test_util;
+
+    /// Types "ab" and a lone escape, which the replay's clock turns into an
+    /// Escape key on the last frame, at 20x5.
+    const TYPING: &[u8] = include_bytes!("../tests/data/typing.input");
+
+    #[test]
+    fn replays_a_capture() {
+        let _lock = test_util::imgui_lock();
+        let mut imgui = crate::context().build();
+        let mut headless = Headless::new((40, 10));
+        let replay = InputReplay::from_reader(TYPING).unwrap();
+        assert_eq!(replay.len(), 4);
+        headless.set_input_replay(Some(replay));
+
+        let mut typed = String::new();
+        while headless.is_replaying() {
+            // Live input is ignored while replaying.
+            headless.new_frame(b"x");
+            for event in headless.events() {
+                match event {
+                    Event::Key(KeyEvent { key: Key::Char(c), .. }) => typed.push(*c),
+                    Event::Key(KeyEvent { key: Key::Escape, .. }) => typed.push_str("<esc>"),
+                    _ => {}
+                }
+            }
+            let ui = imgui.frame();
+            let title = imgui::ImString::new("main");
+            let window = imgui::Window::new(&title)
+                .position([0.0, 0.0], imgui::Condition::Always)
+                .size([20.0, 5.0], imgui::Condition::Always);
+            if let Some(window_token) = window.begin(&ui) {
+                ui.text(format!("typed: {}", typed));
+                window_token.end(&ui);
+            }
+            headless.render(ui.render());
+        }
+
+        let screenshot = headless.screenshot();
+        assert_eq!((screenshot.width, screenshot.height), (20, 5));
+        assert!(headless.text().contains("typed: ab<esc>"), "{}", headless.text());
+    }
+}
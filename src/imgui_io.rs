@@ -0,0 +1,389 @@
+use crate::input::{Event, Key, KeyEvent, MouseButton, MouseEvent, MouseKind};
+use imgui;
+use imgui::sys::ImGuiIO;
+use std::time::{Duration, Instant};
+
+// Key indices follow the ncurses key codes the C++ backend used, so apps that
+// check `ui.is_key_pressed('q' as u32)` keep working.
+const KEY_DOWN: usize = 258;
+const KEY_UP: usize = 259;
+const KEY_LEFT: usize = 260;
+const KEY_RIGHT: usize = 261;
+const KEY_HOME: usize = 262;
+const KEY_BACKSPACE: usize = 263;
+const KEY_F0: usize = 264;
+const KEY_DC: usize = 330;
+const KEY_IC: usize = 331;
+const KEY_NPAGE: usize = 338;
+const KEY_PPAGE: usize = 339;
+const KEY_BTAB: usize = 353;
+const KEY_END: usize = 360;
+
+fn key_index(event: &KeyEvent) -> Option<usize> {
+    let index = match event.key {
+        Key::Char(c) if event.modifiers.ctrl && c.is_ascii_alphabetic() => {
+            (c.to_ascii_lowercase() as u8 - b'a' + 1) as usize
+        }
+        Key::Char(c) if (c as u32) < 256 => c as usize,
+        Key::Char(_) => return None,
+        Key::Tab => 9,
+        Key::BackTab => KEY_BTAB,
+        Key::Enter => 10,
+        Key::Escape => 27,
+        Key::Backspace => KEY_BACKSPACE,
+        Key::Delete => KEY_DC,
+        Key::Insert => KEY_IC,
+        Key::Left => KEY_LEFT,
+        Key::Right => KEY_RIGHT,
+        Key::Up => KEY_UP,
+        Key::Down => KEY_DOWN,
+        Key::Home => KEY_HOME,
+        Key::End => KEY_END,
+        Key::PageUp => KEY_PPAGE,
+        Key::PageDown => KEY_NPAGE,
+        Key::F(n) => KEY_F0 + n as usize,
+    };
+    Some(index)
+}
+
+fn io() -> &'static mut ImGuiIO {
+    unsafe { &mut *imgui::sys::igGetIO() }
+}
+
+fn add_input_character(c: char) {
+    unsafe {
+        imgui::sys::ImGuiIO_AddInputCharacter(imgui::sys::igGetIO(), c as u32);
+    }
+}
+
+/// The last press, for counting double and triple clicks.
+struct Click {
+    button: MouseButton,
+    x: u16,
+    y: u16,
+    time: Duration,
+    count: u8,
+}
+
+/// Feeds terminal input events into the IO of the current imgui context.
+///
+/// A terminal can report a press and release, or several clicks, between two
+/// frames. imgui only sees the button state once per frame, so mouse events
+/// that would change a button twice in one frame are deferred to the next.
+pub(crate) struct ImGuiInput {
+    mouse_pos: [f32; 2],
+    mouse_down: [bool; 3],
+    deferred: Vec<MouseEvent>,
+    last_click: Option<Click>,
+    last_frame: Instant,
+    /// The sum of the frame times so far, which replayed frames advance by
+    /// their captured time.
+    time: Duration,
+}
+
+impl ImGuiInput {
+    pub fn new() -> ImGuiInput {
+        let io = io();
+        let key_map = [
+            (imgui::sys::ImGuiKey_Tab, 9),
+            (imgui::sys::ImGuiKey_LeftArrow, KEY_LEFT),
+            (imgui::sys::ImGuiKey_RightArrow, KEY_RIGHT),
+            (imgui::sys::ImGuiKey_UpArrow, KEY_UP),
+            (imgui::sys::ImGuiKey_DownArrow, KEY_DOWN),
+            (imgui::sys::ImGuiKey_PageUp, KEY_PPAGE),
+            (imgui::sys::ImGuiKey_PageDown, KEY_NPAGE),
+            (imgui::sys::ImGuiKey_Home, KEY_HOME),
+            (imgui::sys::ImGuiKey_End, KEY_END),
+            (imgui::sys::ImGuiKey_Insert, KEY_IC),
+            (imgui::sys::ImGuiKey_Delete, KEY_DC),
+            (imgui::sys::ImGuiKey_Backspace, KEY_BACKSPACE),
+            (imgui::sys::ImGuiKey_Space, 32),
+            (imgui::sys::ImGuiKey_Enter, 10),
+            (imgui::sys::ImGuiKey_Escape, 27),
+            (imgui::sys::ImGuiKey_A, 1),
+            (imgui::sys::ImGuiKey_C, 3),
+            (imgui::sys::ImGuiKey_V, 22),
+            (imgui::sys::ImGuiKey_X, 24),
+            (imgui::sys::ImGuiKey_Y, 25),
+            (imgui::sys::ImGuiKey_Z, 26),
+        ];
+        for (key, index) in key_map.iter() {
+            io.KeyMap[*key as usize] = *index as i32;
+        }
+
+        ImGuiInput {
+            mouse_pos: [-1.0, -1.0],
+            mouse_down: [false; 3],
+            deferred: vec![],
+            last_click: None,
+            last_frame: Instant::now(),
+            time: Duration::from_secs(0),
+        }
+    }
+
+    /// Starts a new imgui frame with the given display size and the events
+    /// received since the last frame. Mouse presses in `events` get their
+    /// click counts filled in.
+    ///
+    /// The frame time is measured, unless `delta` gives it. Returns the frame
+    /// time used.
+    pub fn new_frame(&mut self, display_size: [f32; 2], events: &mut [Event], delta: Option<Duration>) -> Duration {
+        let io = io();
+        let now = Instant::now();
+        let delta = delta.unwrap_or_else(|| now.duration_since(self.last_frame));
+        io.DeltaTime = delta.as_secs_f32().max(1.0e-4);
+        self.last_frame = now;
+        self.time += delta;
+        let now = self.time;
+        io.DisplaySize = imgui::sys::ImVec2 {
+            x: display_size[0],
+            y: display_size[1],
+        };
+
+        // Terminals don't report key releases, so keys are held down for the
+        // one frame they were received in.
+        for down in io.KeysDown.iter_mut() {
+            *down = false;
+        }
+        io.KeyCtrl = false;
+        io.KeyShift = false;
+        io.KeyAlt = false;
+
+        let double_click_time = Duration::from_secs_f32(io.MouseDoubleClickTime.max(0.0));
+        let mut mouse_events = std::mem::replace(&mut self.deferred, vec![]);
+        for event in events.iter_mut() {
+            match event {
+                Event::Key(key) => {
+                    if let Some(index) = key_index(key) {
+                        io.KeysDown[index] = true;
+                    }
+                    io.KeyCtrl |= key.modifiers.ctrl;
+                    io.KeyShift |= key.modifiers.shift;
+                    io.KeyAlt |= key.modifiers.alt;
+                    if let Key::Char(c) = key.key {
+                        if !key.modifiers.ctrl && !key.modifiers.alt {
+                            add_input_character(c);
+                        }
+                    }
+                }
+                Event::Mouse(mouse) => {
+                    self.count_clicks(mouse, now, double_click_time);
+                    mouse_events.push(*mouse);
+                }
+                Event::Paste(text) => {
+                    for c in text.chars() {
+                        add_input_character(c);
+                    }
+                }
+                Event::FocusGained | Event::FocusLost => {}
+            }
+        }
+
+        let mut changed = [false; 3];
+        for (i, mouse) in mouse_events.iter().enumerate() {
+            let buttons = match mouse.kind {
+                MouseKind::Press(button) | MouseKind::Release(Some(button)) => vec![button_index(button)],
+                MouseKind::Release(None) => (0..3).filter(|&b| self.mouse_down[b]).collect(),
+                _ => vec![],
+            };
+            if buttons.iter().any(|&b| changed[b]) {
+                self.deferred = mouse_events[i..].to_vec();
+                break;
+            }
+            for b in buttons {
+                self.mouse_down[b] = match mouse.kind {
+                    MouseKind::Press(_) => true,
+                    _ => false,
+                };
+                changed[b] = true;
+            }
+
+            match mouse.kind {
+                MouseKind::ScrollUp => io.MouseWheel += 1.0,
+                MouseKind::ScrollDown => io.MouseWheel -= 1.0,
+                MouseKind::ScrollLeft => io.MouseWheelH += 1.0,
+                MouseKind::ScrollRight => io.MouseWheelH -= 1.0,
+                _ => {}
+            }
+            self.mouse_pos = [mouse.x as f32, mouse.y as f32];
+            io.KeyCtrl |= mouse.modifiers.ctrl;
+            io.KeyShift |= mouse.modifiers.shift;
+            io.KeyAlt |= mouse.modifiers.alt;
+        }
+
+        io.MousePos = imgui::sys::ImVec2 {
+            x: self.mouse_pos[0],
+            y: self.mouse_pos[1],
+        };
+        io.MouseDown[..3].copy_from_slice(&self.mouse_down);
+        delta
+    }
+
+    /// Counts presses of the same button on the same cell, each within the
+    /// double click time of the last, as double and triple clicks.
+    fn count_clicks(&mut self, mouse: &mut MouseEvent, now: Duration, double_click_time: Duration) {
+        let button = match mouse.kind {
+            MouseKind::Press(button) => button,
+            _ => return,
+        };
+        let count = match &self.last_click {
+            Some(click)
+                if click.button == button
+                    && click.x == mouse.x
+                    && click.y == mouse.y
+                    && now - click.time <= double_click_time =>
+            {
+                click.count % 3 + 1
+            }
+            _ => 1,
+        };
+        mouse.clicks = count;
+        self.last_click = Some(Click {
+            button,
+            x: mouse.x,
+            y: mouse.y,
+            time: now,
+            count,
+        });
+    }
+}
+
+fn button_index(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Right => 1,
+        MouseButton::Middle => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Modifiers;
+    use crate::test_util;
+
+    fn mouse(kind: MouseKind, x: u16, y: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            x,
+            y,
+            modifiers: Modifiers::default(),
+            clicks: 0,
+        }
+    }
+
+    fn press(x: u16, y: u16) -> MouseEvent {
+        mouse(MouseKind::Press(MouseButton::Left), x, y)
+    }
+
+    #[test]
+    fn counts_double_and_triple_clicks() {
+        // Only the click counter is used, which needs no imgui context.
+        let mut input = ImGuiInput {
+            mouse_pos: [-1.0, -1.0],
+            mouse_down: [false; 3],
+            deferred: vec![],
+            last_click: None,
+            last_frame: Instant::now(),
+            time: Duration::from_secs(0),
+        };
+        let double_click_time = Duration::from_millis(300);
+        let mut clicks = |mut event: MouseEvent, ms: u64| {
+            input.count_clicks(&mut event, Duration::from_millis(ms), double_click_time);
+            event.clicks
+        };
+
+        assert_eq!(clicks(press(1, 1), 0), 1);
+        assert_eq!(clicks(press(1, 1), 200), 2);
+        assert_eq!(clicks(press(1, 1), 500), 3);
+        // A fourth click starts over.
+        assert_eq!(clicks(press(1, 1), 700), 1);
+        // Too slow.
+        assert_eq!(clicks(press(1, 1), 1001), 1);
+        // Another cell.
+        assert_eq!(clicks(press(2, 1), 1100), 1);
+        // Another button.
+        assert_eq!(clicks(mouse(MouseKind::Press(MouseButton::Right), 2, 1), 1200), 1);
+        // Only presses are counted.
+        assert_eq!(clicks(mouse(MouseKind::Release(Some(MouseButton::Right)), 2, 1), 1250), 0);
+        assert_eq!(clicks(mouse(MouseKind::Press(MouseButton::Right), 2, 1), 1300), 2);
+    }
+
+    #[test]
+    fn defers_a_second_change_of_a_button_to_the_next_frame() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut input = ImGuiInput::new();
+        let delta = Some(Duration::from_millis(16));
+
+        let mut events = vec![
+            Event::Mouse(press(3, 2)),
+            Event::Mouse(mouse(MouseKind::Release(Some(MouseButton::Left)), 3, 2)),
+            Event::Mouse(press(3, 2)),
+        ];
+        input.new_frame([80.0, 24.0], &mut events, delta);
+        assert!(io().MouseDown[0]);
+        assert_eq!((io().MousePos.x, io().MousePos.y), (3.0, 2.0));
+        let clicks: Vec<u8> = events
+            .iter()
+            .map(|event| match event {
+                Event::Mouse(mouse) => mouse.clicks,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(clicks, vec![1, 0, 2]);
+
+        input.new_frame([80.0, 24.0], &mut [], delta);
+        assert!(!io().MouseDown[0]);
+        input.new_frame([80.0, 24.0], &mut [], delta);
+        assert!(io().MouseDown[0]);
+        input.new_frame([80.0, 24.0], &mut [], delta);
+        assert!(io().MouseDown[0]);
+    }
+
+    #[test]
+    fn releases_every_held_button_for_x10_releases() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut input = ImGuiInput::new();
+        let delta = Some(Duration::from_millis(16));
+
+        let mut events = vec![
+            Event::Mouse(press(0, 0)),
+            Event::Mouse(mouse(MouseKind::Press(MouseButton::Right), 0, 0)),
+        ];
+        input.new_frame([80.0, 24.0], &mut events, delta);
+        assert_eq!(&io().MouseDown[..3], &[true, true, false]);
+
+        let mut events = vec![Event::Mouse(mouse(MouseKind::Release(None), 0, 0))];
+        input.new_frame([80.0, 24.0], &mut events, delta);
+        assert_eq!(&io().MouseDown[..3], &[false, false, false]);
+    }
+
+    #[test]
+    fn drags_and_scrolls() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut input = ImGuiInput::new();
+        let delta = Some(Duration::from_millis(16));
+
+        input.new_frame([80.0, 24.0], &mut [Event::Mouse(press(1, 1))], delta);
+        let mut events = vec![
+            Event::Mouse(mouse(MouseKind::Drag(MouseButton::Left), 4, 2)),
+            Event::Mouse(mouse(MouseKind::Drag(MouseButton::Left), 6, 3)),
+        ];
+        input.new_frame([80.0, 24.0], &mut events, delta);
+        assert!(io().MouseDown[0]);
+        assert_eq!((io().MousePos.x, io().MousePos.y), (6.0, 3.0));
+
+        io().MouseWheel = 0.0;
+        io().MouseWheelH = 0.0;
+        let mut events = vec![
+            Event::Mouse(mouse(MouseKind::ScrollDown, 6, 3)),
+            Event::Mouse(mouse(MouseKind::ScrollDown, 6, 3)),
+            Event::Mouse(mouse(MouseKind::ScrollLeft, 6, 3)),
+        ];
+        input.new_frame([80.0, 24.0], &mut events, delta);
+        assert_eq!((io().MouseWheel, io().MouseWheelH), (-2.0, 1.0));
+    }
+}
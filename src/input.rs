@@ -0,0 +1,702 @@
+//! Decoding of the bytes a terminal sends into input events.
+
+use std;
+use std::time::{Duration, Instant};
+
+/// A key on the keyboard. Printable keys, including space, are `Char`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Tab,
+    BackTab,
+    Enter,
+    Escape,
+    Backspace,
+    Delete,
+    Insert,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    F(u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    /// Decodes the modifier parameter of a CSI sequence, which is one more
+    /// than a bit mask of shift, alt and ctrl.
+    fn from_param(param: u32) -> Modifiers {
+        let bits = param.saturating_sub(1);
+        Modifiers {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            ctrl: bits & 4 != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    fn new(key: Key) -> KeyEvent {
+        KeyEvent {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseKind {
+    Press(MouseButton),
+    /// A button was released. X10 mouse reports don't say which one.
+    Release(Option<MouseButton>),
+    /// The mouse moved with no button held.
+    Move,
+    /// The mouse moved while a button was held.
+    Drag(MouseButton),
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    pub kind: MouseKind,
+    /// Column of the mouse, starting at 0.
+    pub x: u16,
+    /// Row of the mouse, starting at 0.
+    pub y: u16,
+    pub modifiers: Modifiers,
+    /// For presses, 2 for a double click and 3 for a triple click. 0 for any
+    /// other kind of event.
+    pub clicks: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    /// Text pasted into the terminal while bracketed paste was enabled.
+    Paste(String),
+    /// The terminal window gained focus.
+    FocusGained,
+    /// The terminal window lost focus.
+    FocusLost,
+}
+
+const ESC: u8 = 0x1b;
+/// How long an escape, or an escape sequence that is still incomplete, waits
+/// for the rest of the sequence before it counts as typed keys. Sequences
+/// split across reads, e.g. over a slow SSH link, arrive well within this.
+const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+enum Parse {
+    /// The bytes so far are a prefix of a longer sequence.
+    Incomplete,
+    /// Consumed this many bytes, producing an event if the sequence meant
+    /// anything.
+    Done(usize, Option<Event>),
+    /// Consumed the marker that starts a bracketed paste.
+    PasteStart(usize),
+}
+
+/// Turns the bytes read from a terminal into events.
+///
+/// Sequences may be split across calls to `feed`, so bytes that could still
+/// become a longer sequence are held back until more input arrives.
+pub(crate) struct Parser {
+    pending: Vec<u8>,
+    paste: Option<Vec<u8>>,
+    /// When the incomplete escape sequence in `pending` arrived.
+    escape_at: Option<Instant>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            pending: vec![],
+            paste: None,
+            escape_at: None,
+        }
+    }
+
+    /// Decodes `bytes`, read at `now`, appending the events to `events`.
+    pub fn feed(&mut self, bytes: &[u8], now: Instant, events: &mut Vec<Event>) {
+        self.pending.extend_from_slice(bytes);
+
+        let mut start = 0;
+        while start < self.pending.len() {
+            if let Some(paste) = &mut self.paste {
+                let rest = &self.pending[start..];
+                match find(rest, PASTE_END) {
+                    Some(end) => {
+                        paste.extend_from_slice(&rest[..end]);
+                        let text = String::from_utf8_lossy(paste).replace("\r\n", "\n").replace('\r', "\n");
+                        events.push(Event::Paste(text));
+                        self.paste = None;
+                        start += end + PASTE_END.len();
+                    }
+                    None => {
+                        // Keep anything that could be the start of the end
+                        // marker for the next call.
+                        let keep = (1..PASTE_END.len())
+                            .rev()
+                            .find(|&n| rest.ends_with(&PASTE_END[..n]))
+                            .unwrap_or(0);
+                        paste.extend_from_slice(&rest[..rest.len() - keep]);
+                        start = self.pending.len() - keep;
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            match parse(&self.pending[start..]) {
+                Parse::Incomplete => break,
+                Parse::Done(n, event) => {
+                    if let Some(event) = event {
+                        events.push(event);
+                    }
+                    start += n;
+                }
+                Parse::PasteStart(n) => {
+                    self.paste = Some(vec![]);
+                    start += n;
+                }
+            }
+        }
+        self.pending.drain(..start);
+
+        if self.paste.is_none() && self.pending.first() == Some(&ESC) {
+            // Only new bytes can leave a new incomplete sequence behind.
+            if !bytes.is_empty() || self.escape_at.is_none() {
+                self.escape_at = Some(now);
+            }
+        } else {
+            self.escape_at = None;
+        }
+    }
+
+    /// Gives up on an incomplete escape sequence once nothing completed it
+    /// for `ESC_TIMEOUT`. A lone escape becomes the escape key, and `ESC [`
+    /// or `ESC O` becomes Alt+`[` or Alt+`O`, with the bytes after it decoded
+    /// as typed. Call once no more input is immediately available.
+    pub fn flush(&mut self, now: Instant, events: &mut Vec<Event>) {
+        match self.escape_deadline() {
+            Some(deadline) if now >= deadline => {
+                let pending = std::mem::take(&mut self.pending);
+                self.escape_at = None;
+                let rest = match pending.get(1) {
+                    Some(&c) if c == b'[' || c == b'O' => {
+                        let modifiers = Modifiers {
+                            alt: true,
+                            ..Modifiers::default()
+                        };
+                        let key = Key::Char(c as char);
+                        events.push(Event::Key(KeyEvent { key, modifiers }));
+                        &pending[2..]
+                    }
+                    _ => {
+                        events.push(Event::Key(KeyEvent::new(Key::Escape)));
+                        &pending[1..]
+                    }
+                };
+                self.feed(rest, now, events);
+            }
+            _ => {}
+        }
+    }
+
+    /// When an incomplete escape sequence that is waiting for more bytes
+    /// will be given up on, so the caller can wake up for it.
+    pub fn escape_deadline(&self) -> Option<Instant> {
+        self.escape_at.map(|at| at + ESC_TIMEOUT)
+    }
+}
+
+fn parse(bytes: &[u8]) -> Parse {
+    match bytes[0] {
+        ESC => {
+            if bytes.len() == 1 {
+                return Parse::Incomplete;
+            }
+            match bytes[1] {
+                b'[' => parse_csi(bytes),
+                b'O' => parse_ss3(bytes),
+                ESC => Parse::Done(1, Some(Event::Key(KeyEvent::new(Key::Escape)))),
+                _ => match parse_char(&bytes[1..]) {
+                    Parse::Done(n, Some(Event::Key(mut key))) => {
+                        key.modifiers.alt = true;
+                        Parse::Done(n + 1, Some(Event::Key(key)))
+                    }
+                    Parse::Done(n, event) => Parse::Done(n + 1, event),
+                    other => other,
+                },
+            }
+        }
+        _ => parse_char(bytes),
+    }
+}
+
+fn parse_csi(bytes: &[u8]) -> Parse {
+    if bytes.starts_with(PASTE_START) {
+        return Parse::PasteStart(PASTE_START.len());
+    }
+    if bytes.len() >= 3 && bytes[2] == b'M' {
+        return parse_x10_mouse(bytes);
+    }
+
+    let end = match bytes[2..].iter().position(|&b| (0x40..=0x7e).contains(&b)) {
+        Some(end) => end + 2,
+        None => return Parse::Incomplete,
+    };
+    if bytes[2] == b'<' {
+        return parse_sgr_mouse(bytes, end);
+    }
+    let params: Vec<u32> = std::str::from_utf8(&bytes[2..end])
+        .unwrap_or("")
+        .split(';')
+        .map(|p| p.parse().unwrap_or(0))
+        .collect();
+    let modifiers = Modifiers::from_param(params.get(1).cloned().unwrap_or(1));
+    if end == 2 {
+        match bytes[end] {
+            b'I' => return Parse::Done(3, Some(Event::FocusGained)),
+            b'O' => return Parse::Done(3, Some(Event::FocusLost)),
+            _ => {}
+        }
+    }
+
+    let key = match bytes[end] {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'Z' => Some(Key::BackTab),
+        b'P' => Some(Key::F(1)),
+        b'Q' => Some(Key::F(2)),
+        b'R' => Some(Key::F(3)),
+        b'S' => Some(Key::F(4)),
+        b'~' => match params[0] {
+            1 | 7 => Some(Key::Home),
+            2 => Some(Key::Insert),
+            3 => Some(Key::Delete),
+            4 | 8 => Some(Key::End),
+            5 => Some(Key::PageUp),
+            6 => Some(Key::PageDown),
+            11..=15 => Some(Key::F(params[0] as u8 - 10)),
+            17..=21 => Some(Key::F(params[0] as u8 - 11)),
+            23 | 24 => Some(Key::F(params[0] as u8 - 12)),
+            _ => None,
+        },
+        _ => None,
+    };
+    Parse::Done(end + 1, key.map(|key| Event::Key(KeyEvent { key, modifiers })))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_ss3(bytes: &[u8]) -> Parse {
+    if bytes.len() < 3 {
+        return Parse::Incomplete;
+    }
+    let key = match bytes[2] {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'P' => Some(Key::F(1)),
+        b'Q' => Some(Key::F(2)),
+        b'R' => Some(Key::F(3)),
+        b'S' => Some(Key::F(4)),
+        _ => None,
+    };
+    Parse::Done(3, key.map(|key| Event::Key(KeyEvent::new(key))))
+}
+
+/// Decodes the button byte shared by X10 and SGR mouse reports.
+fn mouse_event(cb: u32, x: u16, y: u16, release: bool) -> MouseEvent {
+    let button = match cb & 3 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    };
+    let kind = if cb & 64 != 0 {
+        match cb & 3 {
+            0 => MouseKind::ScrollUp,
+            1 => MouseKind::ScrollDown,
+            2 => MouseKind::ScrollLeft,
+            _ => MouseKind::ScrollRight,
+        }
+    } else if cb & 32 != 0 {
+        match button {
+            Some(button) => MouseKind::Drag(button),
+            None => MouseKind::Move,
+        }
+    } else {
+        match button {
+            Some(button) if !release => MouseKind::Press(button),
+            button => MouseKind::Release(button),
+        }
+    };
+    let modifiers = Modifiers {
+        shift: cb & 4 != 0,
+        alt: cb & 8 != 0,
+        ctrl: cb & 16 != 0,
+    };
+    let clicks = match kind {
+        MouseKind::Press(_) => 1,
+        _ => 0,
+    };
+    MouseEvent {
+        kind,
+        x,
+        y,
+        modifiers,
+        clicks,
+    }
+}
+
+/// `ESC [ M` followed by the button and the coordinates, each offset by 32.
+fn parse_x10_mouse(bytes: &[u8]) -> Parse {
+    if bytes.len() < 6 {
+        return Parse::Incomplete;
+    }
+    let cb = bytes[3].wrapping_sub(32) as u32;
+    let x = bytes[4].wrapping_sub(33) as u16;
+    let y = bytes[5].wrapping_sub(33) as u16;
+    Parse::Done(6, Some(Event::Mouse(mouse_event(cb, x, y, false))))
+}
+
+/// `ESC [ < button ; x ; y` followed by `M` for presses and `m` for releases.
+/// Unlike X10 reports, coordinates aren't limited to 223 cells.
+fn parse_sgr_mouse(bytes: &[u8], end: usize) -> Parse {
+    let params: Vec<u32> = std::str::from_utf8(&bytes[3..end])
+        .unwrap_or("")
+        .split(';')
+        .map(|p| p.parse().unwrap_or(0))
+        .collect();
+    if params.len() != 3 || params[1] == 0 || params[2] == 0 {
+        return Parse::Done(end + 1, None);
+    }
+    let (x, y) = ((params[1] - 1) as u16, (params[2] - 1) as u16);
+    let event = mouse_event(params[0], x, y, bytes[end] == b'm');
+    Parse::Done(end + 1, Some(Event::Mouse(event)))
+}
+
+/// Decodes a single UTF-8 character or control code.
+fn parse_char(bytes: &[u8]) -> Parse {
+    let key = match bytes[0] {
+        b'\t' => Key::Tab,
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        ESC => Key::Escape,
+        // Ctrl+Space, and Ctrl+@ on some terminals.
+        0x00 => {
+            let modifiers = Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            };
+            return Parse::Done(1, Some(Event::Key(KeyEvent { key: Key::Char(' '), modifiers })));
+        }
+        c @ 0x01..=0x1a => {
+            let modifiers = Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            };
+            let key = Key::Char((b'a' + c - 1) as char);
+            return Parse::Done(1, Some(Event::Key(KeyEvent { key, modifiers })));
+        }
+        0x00..=0x1f => return Parse::Done(1, None),
+        c => {
+            let len = match c {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return Parse::Done(1, None),
+            };
+            if bytes.len() < len {
+                return Parse::Incomplete;
+            }
+            match std::str::from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next()) {
+                Some(c) => return Parse::Done(len, Some(Event::Key(KeyEvent::new(Key::Char(c))))),
+                None => return Parse::Done(1, None),
+            }
+        }
+    };
+    Parse::Done(1, Some(Event::Key(KeyEvent::new(key))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: Key) -> Event {
+        Event::Key(KeyEvent::new(key))
+    }
+
+    fn with(key: Key, ctrl: bool, alt: bool, shift: bool) -> Event {
+        let modifiers = Modifiers { ctrl, alt, shift };
+        Event::Key(KeyEvent { key, modifiers })
+    }
+
+    fn decode(chunks: &[&[u8]]) -> Vec<Event> {
+        let mut parser = Parser::new();
+        let mut events = vec![];
+        let now = Instant::now();
+        for chunk in chunks {
+            parser.feed(chunk, now, &mut events);
+        }
+        events
+    }
+
+    #[test]
+    fn decodes_keys_whole_and_split_across_reads() {
+        let keys: &[(&[u8], Event)] = &[
+            (b"a", key(Key::Char('a'))),
+            ("é".as_bytes(), key(Key::Char('é'))),
+            (b"\r", key(Key::Enter)),
+            (b"\t", key(Key::Tab)),
+            (b"\x7f", key(Key::Backspace)),
+            (b"\x00", with(Key::Char(' '), true, false, false)),
+            (b"\x01", with(Key::Char('a'), true, false, false)),
+            (b"\x1ba", with(Key::Char('a'), false, true, false)),
+            (b"\x1b[A", key(Key::Up)),
+            (b"\x1b[1;2A", with(Key::Up, false, false, true)),
+            (b"\x1b[1;5C", with(Key::Right, true, false, false)),
+            (b"\x1b[1;8H", with(Key::Home, true, true, true)),
+            (b"\x1b[Z", key(Key::BackTab)),
+            (b"\x1b[3~", key(Key::Delete)),
+            (b"\x1b[3;3~", with(Key::Delete, false, true, false)),
+            (b"\x1b[5~", key(Key::PageUp)),
+            (b"\x1b[6~", key(Key::PageDown)),
+            (b"\x1b[4~", key(Key::End)),
+            (b"\x1b[15~", key(Key::F(5))),
+            (b"\x1b[24~", key(Key::F(12))),
+            (b"\x1bOA", key(Key::Up)),
+            (b"\x1bOP", key(Key::F(1))),
+            (b"\x1bOS", key(Key::F(4))),
+            (b"\x1b[I", Event::FocusGained),
+            (b"\x1b[O", Event::FocusLost),
+        ];
+        for (bytes, event) in keys {
+            assert_eq!(decode(&[&bytes[..]]), vec![event.clone()], "{:?}", bytes);
+            let split: Vec<&[u8]> = bytes.chunks(1).collect();
+            assert_eq!(decode(&split), vec![event.clone()], "{:?} split", bytes);
+        }
+    }
+
+    #[test]
+    fn skips_unknown_sequences() {
+        assert_eq!(decode(&[b"\x1b[99~x", b"\x1bOzy"]), vec![key(Key::Char('x')), key(Key::Char('y'))]);
+    }
+
+    #[test]
+    fn decodes_pastes_with_markers_split_across_reads() {
+        assert_eq!(
+            decode(&[b"\x1b[200~hello\r\nworld\x1b[201~"]),
+            vec![Event::Paste("hello\nworld".to_string())]
+        );
+        assert_eq!(
+            decode(&[b"\x1b[20", b"0~he", b"llo\x1b[2", b"01~x"]),
+            vec![Event::Paste("hello".to_string()), key(Key::Char('x'))]
+        );
+        // Part of an end marker that doesn't finish is pasted text.
+        assert_eq!(
+            decode(&[b"\x1b[200~a\x1b[2", b"x\x1b[201~"]),
+            vec![Event::Paste("a\x1b[2x".to_string())]
+        );
+    }
+
+    #[test]
+    fn waits_for_the_escape_timeout() {
+        let mut parser = Parser::new();
+        let mut events = vec![];
+        let start = Instant::now();
+        parser.feed(b"\x1b", start, &mut events);
+        assert_eq!(parser.escape_deadline(), Some(start + ESC_TIMEOUT));
+
+        parser.flush(start + ESC_TIMEOUT / 2, &mut events);
+        assert!(events.is_empty());
+        // Polling without new bytes doesn't restart the timeout.
+        parser.feed(b"", start + ESC_TIMEOUT / 2, &mut events);
+        assert_eq!(parser.escape_deadline(), Some(start + ESC_TIMEOUT));
+
+        parser.flush(start + ESC_TIMEOUT, &mut events);
+        assert_eq!(events, vec![key(Key::Escape)]);
+        assert_eq!(parser.escape_deadline(), None);
+    }
+
+    #[test]
+    fn finishes_sequences_that_arrive_before_the_timeout() {
+        let mut parser = Parser::new();
+        let mut events = vec![];
+        let start = Instant::now();
+        parser.feed(b"\x1b", start, &mut events);
+        parser.feed(b"[A", start + ESC_TIMEOUT / 2, &mut events);
+        parser.flush(start + ESC_TIMEOUT, &mut events);
+        assert_eq!(events, vec![key(Key::Up)]);
+        assert_eq!(parser.escape_deadline(), None);
+    }
+
+    #[test]
+    fn flushes_incomplete_sequences_after_the_timeout() {
+        let cases: &[(&[u8], Vec<Event>)] = &[
+            (b"\x1b[", vec![with(Key::Char('['), false, true, false)]),
+            (b"\x1bO", vec![with(Key::Char('O'), false, true, false)]),
+            (
+                b"\x1b[1;",
+                vec![
+                    with(Key::Char('['), false, true, false),
+                    key(Key::Char('1')),
+                    key(Key::Char(';')),
+                ],
+            ),
+        ];
+        for (bytes, expected) in cases {
+            let mut parser = Parser::new();
+            let mut events = vec![];
+            let start = Instant::now();
+            parser.feed(bytes, start, &mut events);
+            assert!(events.is_empty(), "{:?}", bytes);
+            parser.flush(start + ESC_TIMEOUT, &mut events);
+            assert_eq!(&events, expected, "{:?}", bytes);
+
+            // Later input isn't swallowed by the sequence.
+            events.clear();
+            parser.feed(b"a", start + ESC_TIMEOUT, &mut events);
+            assert_eq!(events, vec![key(Key::Char('a'))], "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn flushes_an_escape_left_after_an_incomplete_sequence() {
+        let mut parser = Parser::new();
+        let mut events = vec![];
+        let start = Instant::now();
+        parser.feed(b"\x1b[\x1b", start, &mut events);
+        parser.flush(start + ESC_TIMEOUT, &mut events);
+        assert_eq!(events, vec![with(Key::Char('['), false, true, false)]);
+        assert_eq!(parser.escape_deadline(), Some(start + ESC_TIMEOUT * 2));
+        parser.flush(start + ESC_TIMEOUT * 2, &mut events);
+        assert_eq!(events.last(), Some(&key(Key::Escape)));
+    }
+
+    fn mouse(kind: MouseKind, x: u16, y: u16) -> Event {
+        let clicks = match kind {
+            MouseKind::Press(_) => 1,
+            _ => 0,
+        };
+        let modifiers = Modifiers::default();
+        Event::Mouse(MouseEvent { kind, x, y, modifiers, clicks })
+    }
+
+    #[test]
+    fn decodes_sgr_mouse_reports() {
+        use self::MouseButton::*;
+        use self::MouseKind::*;
+
+        let reports: &[(&[u8], Event)] = &[
+            (b"\x1b[<0;10;5M", mouse(Press(Left), 9, 4)),
+            (b"\x1b[<1;1;1M", mouse(Press(Middle), 0, 0)),
+            (b"\x1b[<2;1;1M", mouse(Press(Right), 0, 0)),
+            (b"\x1b[<0;10;5m", mouse(Release(Some(Left)), 9, 4)),
+            (b"\x1b[<2;300;200m", mouse(Release(Some(Right)), 299, 199)),
+            (b"\x1b[<32;3;4M", mouse(Drag(Left), 2, 3)),
+            (b"\x1b[<34;3;4M", mouse(Drag(Right), 2, 3)),
+            (b"\x1b[<35;3;4M", mouse(Move, 2, 3)),
+            (b"\x1b[<64;1;1M", mouse(ScrollUp, 0, 0)),
+            (b"\x1b[<65;1;1M", mouse(ScrollDown, 0, 0)),
+            (b"\x1b[<66;1;1M", mouse(ScrollLeft, 0, 0)),
+            (b"\x1b[<67;1;1M", mouse(ScrollRight, 0, 0)),
+        ];
+        for (bytes, event) in reports {
+            assert_eq!(decode(&[&bytes[..]]), vec![event.clone()], "{:?}", bytes);
+            let split: Vec<&[u8]> = bytes.chunks(1).collect();
+            assert_eq!(decode(&split), vec![event.clone()], "{:?} split", bytes);
+        }
+    }
+
+    #[test]
+    fn decodes_mouse_modifiers() {
+        let events = decode(&[b"\x1b[<4;1;1M\x1b[<8;1;1M\x1b[<16;1;1M\x1b[<92;1;1M"]);
+        let decoded: Vec<(MouseKind, Modifiers)> = events
+            .iter()
+            .map(|event| match event {
+                Event::Mouse(mouse) => (mouse.kind, mouse.modifiers),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        let m = |ctrl, alt, shift| Modifiers { ctrl, alt, shift };
+        let press = MouseKind::Press(MouseButton::Left);
+        assert_eq!(
+            decoded,
+            vec![
+                (press, m(false, false, true)),
+                (press, m(false, true, false)),
+                (press, m(true, false, false)),
+                (MouseKind::ScrollUp, m(true, true, true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_sgr_reports_outside_the_screen() {
+        assert_eq!(decode(&[b"\x1b[<0;0;1M\x1b[<0;1Mx"]), vec![key(Key::Char('x'))]);
+    }
+
+    #[test]
+    fn decodes_x10_mouse_reports() {
+        use self::MouseButton::*;
+        use self::MouseKind::*;
+
+        let report = |cb: u8, x: u8, y: u8| vec![ESC, b'[', b'M', cb + 32, x + 33, y + 33];
+        let reports = [
+            (report(0, 9, 4), mouse(Press(Left), 9, 4)),
+            (report(2, 0, 0), mouse(Press(Right), 0, 0)),
+            (report(3, 1, 2), mouse(Release(None), 1, 2)),
+            (report(32, 5, 5), mouse(Drag(Left), 5, 5)),
+            (report(35, 5, 5), mouse(Move, 5, 5)),
+            (report(64, 0, 0), mouse(ScrollUp, 0, 0)),
+            (report(65, 0, 0), mouse(ScrollDown, 0, 0)),
+        ];
+        for (bytes, event) in &reports {
+            assert_eq!(decode(&[&bytes[..]]), vec![event.clone()], "{:?}", bytes);
+            let split: Vec<&[u8]> = bytes.chunks(1).collect();
+            assert_eq!(decode(&split), vec![event.clone()], "{:?} split", bytes);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Tiling the screen into rectangles of whole cells for imgui windows.
+//!
+//! A `Layout` splits a `Rect` into rows or columns by a list of
+//! constraints, and nested layouts split those in turn. Splitting is cheap,
+//! so solve the layout every frame from the display size and it follows
+//! resizes:
+//!
+//! ```ignore
+//! let screen = Rect::screen(ui.io().display_size);
+//! let rows = Layout::vertical(vec![Constraint::Min(1), Constraint::Fixed(4)]).split(screen);
+//! let columns = Layout::horizontal(vec![Constraint::Min(1); 3]).gap(1).split(rows[0]);
+//! imgui::Window::new(im_str!("left"))
+//!     .position(columns[0].position(), imgui::Condition::Always)
+//!     .size(columns[0].size(), imgui::Condition::Always)
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// A rectangle of cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    /// The whole screen, from imgui's `display_size`.
+    pub fn screen(display_size: [f32; 2]) -> Rect {
+        let cells = |v: f32| v.max(0.0).min(std::u16::MAX as f32) as u16;
+        Rect::new(0, 0, cells(display_size[0]), cells(display_size[1]))
+    }
+
+    /// The top left corner, for `imgui::Window::position`.
+    pub fn position(&self) -> [f32; 2] {
+        [self.x as f32, self.y as f32]
+    }
+
+    /// For `imgui::Window::size`.
+    pub fn size(&self) -> [f32; 2] {
+        [self.width as f32, self.height as f32]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn right(&self) -> u16 {
+        self.x.saturating_add(self.width)
+    }
+
+    pub fn bottom(&self) -> u16 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The rectangle shrunk by `margin` cells on every side.
+    pub fn inset(&self, margin: u16) -> Rect {
+        let horizontal = margin.min(self.width / 2);
+        let vertical = margin.min(self.height / 2);
+        Rect::new(
+            self.x + horizontal,
+            self.y + vertical,
+            self.width - horizontal * 2,
+            self.height - vertical * 2,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Side by side, left to right.
+    Horizontal,
+    /// Stacked, top to bottom.
+    Vertical,
+}
+
+/// The size of one part of a layout, along the layout's direction.
+///
+/// When the space runs out, percentages shrink first, then minimums, then
+/// fixed sizes, each from the last part backwards. Spare space goes to the
+/// `Max` parts up to their limits, then is shared evenly by the `Min`
+/// parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// Exactly this many cells.
+    Fixed(u16),
+    /// This percentage of the space left after the gaps.
+    Percentage(u16),
+    /// At least this many cells, and a share of any spare space.
+    Min(u16),
+    /// Up to this many cells of spare space.
+    Max(u16),
+}
+
+/// Splits a rectangle into parts along one direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub direction: Direction,
+    pub constraints: Vec<Constraint>,
+    /// Cells between neighbouring parts.
+    pub gap: u16,
+    /// Cells left empty around the parts.
+    pub margin: u16,
+}
+
+impl Layout {
+    pub fn new(direction: Direction, constraints: Vec<Constraint>) -> Layout {
+        Layout {
+            direction,
+            constraints,
+            gap: 0,
+            margin: 0,
+        }
+    }
+
+    pub fn horizontal(constraints: Vec<Constraint>) -> Layout {
+        Layout::new(Direction::Horizontal, constraints)
+    }
+
+    pub fn vertical(constraints: Vec<Constraint>) -> Layout {
+        Layout::new(Direction::Vertical, constraints)
+    }
+
+    pub fn gap(mut self, gap: u16) -> Layout {
+        self.gap = gap;
+        self
+    }
+
+    pub fn margin(mut self, margin: u16) -> Layout {
+        self.margin = margin;
+        self
+    }
+
+    /// One rectangle per constraint, in order. Parts that get no space are
+    /// empty rectangles at the end of the previous part.
+    pub fn split(&self, area: Rect) -> Vec<Rect> {
+        let area = area.inset(self.margin);
+        let length = match self.direction {
+            Direction::Horizontal => area.width,
+            Direction::Vertical => area.height,
+        };
+        let gaps = (self.gap as u32) * (self.constraints.len().max(1) as u32 - 1);
+        let available = (length as u32).saturating_sub(gaps);
+        let sizes = solve(&self.constraints, available);
+
+        let mut offset = 0u32;
+        let mut rects = Vec::with_capacity(sizes.len());
+        for (i, size) in sizes.into_iter().enumerate() {
+            if i > 0 && size > 0 {
+                offset = (offset + self.gap as u32).min(length as u32);
+            }
+            let start = offset.min(length as u32) as u16;
+            let size = size.min(length as u32 - start as u32) as u16;
+            rects.push(match self.direction {
+                Direction::Horizontal => Rect::new(area.x + start, area.y, size, area.height),
+                Direction::Vertical => Rect::new(area.x, area.y + start, area.width, size),
+            });
+            offset = start as u32 + size as u32;
+        }
+        rects
+    }
+}
+
+/// The length of each part, adding up to at most `available`.
+fn solve(constraints: &[Constraint], available: u32) -> Vec<u32> {
+    let percentage = |p: u16| available * (p.min(100) as u32) / 100;
+    let mut sizes: Vec<u32> = constraints
+        .iter()
+        .map(|constraint| match *constraint {
+            Constraint::Fixed(n) | Constraint::Min(n) => n as u32,
+            Constraint::Percentage(p) => percentage(p),
+            Constraint::Max(_) => 0,
+        })
+        .collect();
+
+    let total: u32 = sizes.iter().sum();
+    if total > available {
+        let mut overflow = total - available;
+        let shrink_order: [fn(&Constraint) -> bool; 3] = [
+            |c| match c {
+                Constraint::Percentage(_) => true,
+                _ => false,
+            },
+            |c| match c {
+                Constraint::Min(_) => true,
+                _ => false,
+            },
+            |c| match c {
+                Constraint::Fixed(_) => true,
+                _ => false,
+            },
+        ];
+        for shrinks in shrink_order.iter() {
+            for i in (0..sizes.len()).rev() {
+                if overflow == 0 {
+                    return sizes;
+                }
+                if shrinks(&constraints[i]) {
+                    let cut = overflow.min(sizes[i]);
+                    sizes[i] -= cut;
+                    overflow -= cut;
+                }
+            }
+        }
+        return sizes;
+    }
+
+    let mut spare = available - total;
+
+    // Max parts grow together, so one doesn't take it all while another
+    // stays empty.
+    loop {
+        let growing: Vec<usize> = (0..sizes.len())
+            .filter(|&i| match constraints[i] {
+                Constraint::Max(limit) => sizes[i] < limit as u32,
+                _ => false,
+            })
+            .collect();
+        if growing.is_empty() || spare == 0 {
+            break;
+        }
+        let share = (spare / growing.len() as u32).max(1);
+        for i in growing {
+            if let Constraint::Max(limit) = constraints[i] {
+                let add = share.min(limit as u32 - sizes[i]).min(spare);
+                sizes[i] += add;
+                spare -= add;
+            }
+        }
+    }
+
+    let mins: Vec<usize> = (0..sizes.len())
+        .filter(|&i| match constraints[i] {
+            Constraint::Min(_) => true,
+            _ => false,
+        })
+        .collect();
+    if !mins.is_empty() {
+        let count = mins.len() as u32;
+        for (n, &i) in mins.iter().enumerate() {
+            sizes[i] += spare / count + if (n as u32) < spare % count { 1 } else { 0 };
+        }
+        return sizes;
+    }
+
+    // Percentages were rounded down; give back the cells that lost.
+    for i in 0..sizes.len() {
+        if spare == 0 {
+            break;
+        }
+        if let Constraint::Percentage(p) = constraints[i] {
+            let exact = (available * (p.min(100) as u32) + 99) / 100;
+            if sizes[i] < exact {
+                sizes[i] += 1;
+                spare -= 1;
+            }
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Constraint::*;
+
+    #[test]
+    fn shrinks_percentages_then_minimums_then_fixed_sizes() {
+        let constraints = [Fixed(10), Min(10), Percentage(50), Min(4)];
+        assert_eq!(solve(&constraints, 30), vec![10, 10, 6, 4]);
+        assert_eq!(solve(&constraints, 24), vec![10, 10, 0, 4]);
+        assert_eq!(solve(&constraints, 20), vec![10, 10, 0, 0]);
+        assert_eq!(solve(&constraints, 15), vec![10, 5, 0, 0]);
+        assert_eq!(solve(&constraints, 6), vec![6, 0, 0, 0]);
+        assert_eq!(solve(&constraints, 0), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn grows_maximums_then_shares_with_minimums() {
+        assert_eq!(solve(&[Max(3), Max(10)], 8), vec![3, 5]);
+        assert_eq!(solve(&[Max(3), Min(2), Min(2)], 12), vec![3, 5, 4]);
+        assert_eq!(solve(&[Fixed(2), Max(3)], 10), vec![2, 3]);
+    }
+
+    #[test]
+    fn rounds_percentages_to_fill_the_space() {
+        assert_eq!(solve(&[Percentage(33), Percentage(33), Percentage(33)], 10), vec![4, 3, 3]);
+        assert_eq!(solve(&[Percentage(50), Percentage(50)], 7), vec![4, 3]);
+        assert_eq!(solve(&[Percentage(25), Percentage(25)], 7), vec![2, 2]);
+        assert_eq!(solve(&[Percentage(150)], 7), vec![7]);
+    }
+
+    #[test]
+    fn splits_with_gaps_and_margins() {
+        let area = Rect::new(0, 0, 12, 4);
+        let rects = Layout::horizontal(vec![Fixed(3), Min(1)]).gap(1).margin(1).split(area);
+        assert_eq!(rects, vec![Rect::new(1, 1, 3, 2), Rect::new(5, 1, 6, 2)]);
+
+        let rects = Layout::vertical(vec![Fixed(3), Fixed(3)]).gap(1).split(Rect::new(0, 0, 5, 4));
+        assert_eq!(rects, vec![Rect::new(0, 0, 5, 3), Rect::new(0, 3, 5, 0)]);
+    }
+}
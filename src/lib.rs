@@ -1,60 +1,167 @@
 use imgui;
-use imgui::internal::{RawCast};
 use std;
+use std::io;
+use std::time::Duration;
 
 pub use imtui_sys::root as sys;
 
+mod ansi;
+mod backend;
+mod capabilities;
+mod clipboard;
+mod color;
+mod context;
+mod curses;
+mod cursor;
+mod export;
+pub mod focus;
+mod frontend;
+mod headless;
+mod imgui_io;
+mod input;
+pub mod layout;
+pub mod log;
+mod overlay;
+pub mod panes;
+mod record;
+mod replay;
+mod screen;
+pub mod session;
+mod settings;
+mod stats;
+mod stream;
+mod terminal;
+#[cfg(test)]
+mod test_util;
+pub mod theme;
+pub mod trace;
+mod tty;
+mod vsync;
+pub mod widgets;
+
+pub use crate::backend::Backend;
+pub use crate::capabilities::Capabilities;
+pub use crate::clipboard::{ClipboardProvider, CommandClipboard};
+pub use crate::color::{ansi256, palette_rgb, DefaultColors};
+pub use crate::context::{apply_terminal_metrics, ContextBuilder, StylePreset};
+pub use crate::cursor::CursorShape;
+pub use crate::export::Screenshot;
+pub use crate::headless::Headless;
+pub use crate::input::{Event, Key, KeyEvent, Modifiers, MouseButton, MouseEvent, MouseKind};
+pub use crate::overlay::DebugOverlay;
+pub use crate::record::Recorder;
+pub use crate::replay::{InputCapture, InputReplay};
+pub use crate::screen::Cell;
+pub use crate::settings::Settings;
+pub use crate::stats::{FrameHistory, FrameStats};
+pub use crate::stream::Stream;
+pub use crate::terminal::{Terminal, TerminalOptions, Viewport};
+pub use crate::tty::TtySource;
+
+/// Starts building an `imgui::Context` with spacing in cells instead of
+/// pixels and no ini file, e.g.
+/// `imtui::context().preset(imtui::StylePreset::Retro).build()`.
+pub fn context() -> ContextBuilder {
+    ContextBuilder::new()
+}
+
 pub struct Ncurses {
-    screen: *mut sys::ImTui::TScreen,
-    is_active: bool,
+    frontend: frontend::Frontend,
+    painter: curses::Painter,
+    capabilities: Capabilities,
+    cursor: cursor::Cursor,
+    mouse_support: bool,
+    input_buf: Vec<u8>,
+    /// Input that arrived while waiting for replies to queries, for the next
+    /// frame.
+    stashed_input: Vec<u8>,
+    // Dropped last, so everything above can still talk to the terminal.
+    session: curses::Session,
 }
 
 impl Ncurses {
-    pub fn init(mouse_support: bool, active_fps: f32, idle_fps: f32) -> Ncurses {
-        let screen: *mut sys::ImTui::TScreen;
-        unsafe {
-            screen = sys::ImTui_ImplNcurses_Init(mouse_support, active_fps, idle_fps);
-            sys::ImTui_ImplText_Init();
-        }
-        Ncurses {
-            screen: screen,
-            is_active: false,
+    pub fn init(mouse_support: bool, active_fps: f32, idle_fps: f32) -> io::Result<Ncurses> {
+        Ncurses::init_with_tty(&TtySource::Stdio, mouse_support, active_fps, idle_fps)
+    }
+
+    /// Like `init`, but runs the UI on the given terminal instead of stdin
+    /// and stdout.
+    pub fn init_with_tty(tty: &TtySource, mouse_support: bool, active_fps: f32, idle_fps: f32) -> io::Result<Ncurses> {
+        let session = curses::Session::new(tty)?;
+        let mut frontend = frontend::Frontend::new(active_fps, idle_fps);
+        curses::send("\x1b[?2004h\x1b[?1004h");
+        if mouse_support {
+            // SGR reports name the released button and aren't limited to 223
+            // columns.
+            curses::send("\x1b[?1003h\x1b[?1006h");
         }
+        let capabilities = Capabilities::detect();
+        frontend.set_clipboard_link(Box::new(curses::Link), capabilities.osc52);
+        Ok(Ncurses {
+            frontend,
+            painter: curses::Painter::new(),
+            capabilities,
+            cursor: cursor::Cursor::new(),
+            mouse_support,
+            input_buf: vec![],
+            stashed_input: vec![],
+            session,
+        })
     }
 
-    pub fn set_active(&mut self) {
-        self.is_active = true;
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
     }
 
-    pub fn set_inactive(&mut self) {
-        self.is_active = false;
+    /// Asks the terminal which features it supports, waiting at most
+    /// `timeout` for the replies. Terminals that don't answer keep the
+    /// capabilities detected from terminfo and the environment.
+    pub fn query_capabilities(&mut self, timeout: Duration) -> &Capabilities {
+        let input = self.capabilities.query(timeout);
+        self.stashed_input.extend_from_slice(&input);
+        self.frontend.set_clipboard_link(Box::new(curses::Link), self.capabilities.osc52);
+        &self.capabilities
     }
 
-    pub fn new_frame(&self) -> bool {
-        let input_pending: bool;
-        unsafe {
-            input_pending = sys::ImTui_ImplNcurses_NewFrame();
-            sys::ImTui_ImplText_NewFrame();
-        }
-        input_pending
+    /// Reads pending input and feeds it to imgui. Returns true if there was
+    /// any input.
+    pub fn new_frame(&mut self) -> bool {
+        self.frontend.stats.begin_input();
+        self.input_buf.clear();
+        self.input_buf.append(&mut self.stashed_input);
+        self.frontend.take_stashed_input(&mut self.input_buf);
+        curses::read_input(&mut self.input_buf);
+        let (nx, ny) = curses::screen_size();
+        self.frontend.new_frame(&self.input_buf, (nx.max(1) as u16, ny.max(1) as u16), 0);
+        !self.input_buf.is_empty()
     }
 
-    pub fn render(&self, draw_data: &imgui::DrawData) {
-        unsafe {
-            let raw_ptr = draw_data.raw() as *const imgui::sys::ImDrawData as *mut imgui::sys::ImDrawData;
-            sys::ImTui_ImplText_RenderDrawData(raw_ptr, self.screen);
-            sys::ImTui_ImplNcurses_DrawScreen(self.is_active);
-        }
+    pub fn render(&mut self, draw_data: &imgui::DrawData) {
+        self.frontend.rasterize(draw_data, curses::input_pending);
+        let colors = self.frontend.colors();
+        self.frontend.stats.current.changed_cells = self.painter.draw(self.frontend.screen(), &colors);
+        self.cursor.update(self.frontend.caret());
+        curses::refresh();
+        self.cursor.apply_shape(self.frontend.cursor_shape);
+        self.frontend.stats.end_frame();
+    }
+}
+
+impl Backend for Ncurses {
+    fn frontend(&self) -> &frontend::Frontend {
+        &self.frontend
+    }
+
+    fn frontend_mut(&mut self) -> &mut frontend::Frontend {
+        &mut self.frontend
     }
 }
 
 impl Drop for Ncurses {
     fn drop(&mut self) {
-        unsafe {
-            sys::ImTui_ImplText_Shutdown();
-            sys::ImTui_ImplNcurses_Shutdown();
+        curses::send("\x1b[?2004l\x1b[?1004l");
+        if self.mouse_support {
+            curses::send("\x1b[?1006l\x1b[?1003l");
         }
-        self.screen = std::ptr::null_mut();
-        self.is_active = false;
     }
 }
@@ -0,0 +1,287 @@
+//! Keeping `log` records in memory and showing them in a console window,
+//! since logging to stderr would draw over the UI.
+
+use ::log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use imgui;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One logged message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    /// Time since the `LogBuffer` was created.
+    pub time: Duration,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+struct Ring {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+}
+
+/// A `log::Log` that keeps the last records in a ring buffer.
+///
+/// Clones share the buffer, so one clone can be installed as the logger
+/// while another is shown by a `LogConsole`.
+#[derive(Clone)]
+pub struct LogBuffer {
+    ring: Arc<Mutex<Ring>>,
+    start: Instant,
+    level: LevelFilter,
+    /// Levels for module path prefixes, which override `level`.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogBuffer {
+    /// Keeps the last `capacity` records of every level.
+    pub fn new(capacity: usize) -> LogBuffer {
+        LogBuffer {
+            ring: Arc::new(Mutex::new(Ring {
+                records: VecDeque::with_capacity(capacity),
+                capacity,
+            })),
+            start: Instant::now(),
+            level: LevelFilter::Trace,
+            modules: vec![],
+        }
+    }
+
+    /// Keeps records up to `level` from modules without their own filter.
+    pub fn filter_level(mut self, level: LevelFilter) -> LogBuffer {
+        self.level = level;
+        self
+    }
+
+    /// Keeps records up to `level` from `module` and its submodules, like
+    /// `env_logger::Builder::filter_module`.
+    pub fn filter_module(mut self, module: &str, level: LevelFilter) -> LogBuffer {
+        self.modules.push((module.to_string(), level));
+        self
+    }
+
+    /// Installs a clone of this buffer as the global logger, and returns the
+    /// buffer to show it with.
+    pub fn install(self) -> Result<LogBuffer, SetLoggerError> {
+        let max_level = self.modules.iter().map(|(_, level)| *level).fold(self.level, |a, b| a.max(b));
+        ::log::set_boxed_logger(Box::new(self.clone()))?;
+        ::log::set_max_level(max_level);
+        Ok(self)
+    }
+
+    /// A copy of the records in the buffer, oldest first.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.ring.lock().unwrap().records.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.lock().unwrap().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.ring.lock().unwrap().records.clear();
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let record = LogRecord {
+            time: self.start.elapsed(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let mut ring = self.ring.lock().unwrap();
+        if ring.capacity == 0 {
+            return;
+        }
+        if ring.records.len() == ring.capacity {
+            ring.records.pop_front();
+        }
+        ring.records.push_back(record);
+    }
+
+    fn flush(&self) {}
+}
+
+/// A window listing the records of a `LogBuffer`, with filters for the
+/// level, the module and the message text. It follows new records while
+/// scrolled to the bottom.
+pub struct LogConsole {
+    pub level: LevelFilter,
+    module: imgui::ImString,
+    search: imgui::ImString,
+}
+
+impl Default for LogConsole {
+    fn default() -> LogConsole {
+        LogConsole {
+            level: LevelFilter::Trace,
+            module: imgui::ImString::with_capacity(64),
+            search: imgui::ImString::with_capacity(64),
+        }
+    }
+}
+
+impl LogConsole {
+    pub fn new() -> LogConsole {
+        LogConsole::default()
+    }
+
+    /// Draws the console in a window of its own.
+    pub fn window(&mut self, ui: &imgui::Ui, title: &imgui::ImStr, buffer: &LogBuffer) {
+        let window = imgui::Window::new(title)
+            .position([0.0, 0.0], imgui::Condition::FirstUseEver)
+            .size([80.0, 15.0], imgui::Condition::FirstUseEver);
+        if let Some(window_token) = window.begin(ui) {
+            self.draw(ui, buffer);
+            window_token.end(ui);
+        }
+    }
+
+    /// Draws the filters and the records into the current window.
+    pub fn draw(&mut self, ui: &imgui::Ui, buffer: &LogBuffer) {
+        let level_label = imgui::ImString::new(format!("{:5}##level", self.level.to_string()));
+        if ui.button(&level_label, [0.0, 0.0]) {
+            self.level = next_level(self.level);
+        }
+        ui.same_line(0.0);
+        let clear_label = imgui::ImString::new("Clear");
+        if ui.button(&clear_label, [0.0, 0.0]) {
+            buffer.clear();
+        }
+        ui.same_line(0.0);
+        ui.input_text(&imgui::ImString::new("Module"), &mut self.module).build();
+        ui.input_text(&imgui::ImString::new("Search"), &mut self.search).build();
+
+        let module = self.module.to_str().to_string();
+        let search = self.search.to_str().to_lowercase();
+        let records = buffer.records();
+        let child_id = imgui::ImString::new("##log-records");
+        imgui::ChildWindow::new(&child_id).size([0.0, 0.0]).build(ui, || {
+            for record in &records {
+                if record.level > self.level
+                    || !record.target.contains(module.as_str())
+                    || !record.message.to_lowercase().contains(search.as_str())
+                {
+                    continue;
+                }
+                ui.text_colored(
+                    level_color(record.level),
+                    format!(
+                        "{:8.3} {:5} {}: {}",
+                        record.time.as_secs_f32(),
+                        record.level,
+                        record.target,
+                        record.message
+                    ),
+                );
+            }
+            unsafe {
+                if imgui::sys::igGetScrollY() >= imgui::sys::igGetScrollMaxY() {
+                    imgui::sys::igSetScrollHereY(1.0);
+                }
+            }
+        });
+    }
+}
+
+fn next_level(level: LevelFilter) -> LevelFilter {
+    match level {
+        LevelFilter::Off | LevelFilter::Trace => LevelFilter::Error,
+        LevelFilter::Error => LevelFilter::Warn,
+        LevelFilter::Warn => LevelFilter::Info,
+        LevelFilter::Info => LevelFilter::Debug,
+        LevelFilter::Debug => LevelFilter::Trace,
+    }
+}
+
+fn level_color(level: Level) -> [f32; 4] {
+    match level {
+        Level::Error => [1.0, 0.3, 0.3, 1.0],
+        Level::Warn => [1.0, 0.8, 0.2, 1.0],
+        Level::Info => [0.9, 0.9, 0.9, 1.0],
+        Level::Debug => [0.6, 0.8, 1.0, 1.0],
+        Level::Trace => [0.6, 0.6, 0.6, 1.0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(buffer: &LogBuffer, level: Level, target: &str, message: &str) {
+        buffer.log(&Record::builder().level(level).target(target).args(format_args!("{}", message)).build());
+    }
+
+    fn messages(buffer: &LogBuffer) -> Vec<String> {
+        buffer.records().into_iter().map(|record| record.message).collect()
+    }
+
+    #[test]
+    fn keeps_the_last_records() {
+        let buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            log(&buffer, Level::Info, "app", &i.to_string());
+        }
+        assert_eq!(messages(&buffer), vec!["2", "3", "4"]);
+        assert_eq!(buffer.records()[0].target, "app");
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        log(&buffer, Level::Info, "app", "5");
+        assert_eq!(messages(&buffer), vec!["5"]);
+
+        let empty = LogBuffer::new(0);
+        log(&empty, Level::Error, "app", "dropped");
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn uses_the_most_specific_module_filter() {
+        let buffer = LogBuffer::new(16)
+            .filter_level(LevelFilter::Warn)
+            .filter_module("app::net", LevelFilter::Debug)
+            .filter_module("app", LevelFilter::Info)
+            .filter_module("app::net::tls", LevelFilter::Error);
+        assert_eq!(buffer.level_for("other"), LevelFilter::Warn);
+        assert_eq!(buffer.level_for("app"), LevelFilter::Info);
+        assert_eq!(buffer.level_for("app::ui"), LevelFilter::Info);
+        assert_eq!(buffer.level_for("app::net"), LevelFilter::Debug);
+        assert_eq!(buffer.level_for("app::net::http"), LevelFilter::Debug);
+        assert_eq!(buffer.level_for("app::net::tls"), LevelFilter::Error);
+        // Prefixes only match whole module names.
+        assert_eq!(buffer.level_for("application"), LevelFilter::Warn);
+        assert_eq!(buffer.level_for("app::network"), LevelFilter::Info);
+
+        log(&buffer, Level::Debug, "app::net::http", "kept");
+        log(&buffer, Level::Debug, "app::ui", "dropped");
+        log(&buffer, Level::Warn, "app::net::tls", "dropped");
+        log(&buffer, Level::Warn, "other", "kept too");
+        assert_eq!(messages(&buffer), vec!["kept", "kept too"]);
+    }
+}
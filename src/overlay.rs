@@ -0,0 +1,283 @@
+//! A debug panel drawn over the rasterized screen.
+
+use crate::input::Event;
+use crate::stats::{FrameHistory, FrameStats};
+use crate::sys;
+use imgui;
+use imgui::internal::RawCast;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+
+const PANEL_WIDTH: usize = 46;
+const TITLE_COLORS: (u8, u8) = (231, 24);
+const PANEL_COLORS: (u8, u8) = (252, 236);
+/// Background of cells that changed since the previous frame.
+const CHANGED_BG: u8 = 52;
+
+/// A panel with frame stats, the screen and mouse position, the imgui
+/// windows and the last input events, drawn by the backend on top of the
+/// app's frame.
+///
+/// The overlay is drawn into the screen cells after imgui's draw data is
+/// rasterized, so it works without any changes to the app's UI code. Toggle
+/// it with `visible`, e.g. from a hotkey.
+#[derive(Clone, Debug)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    /// Tints the cells that changed since the previous frame.
+    pub highlight_changes: bool,
+    /// How many input events to list.
+    pub event_count: usize,
+    events: VecDeque<String>,
+    prev: Vec<sys::ImTui::TCell>,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay {
+            visible: false,
+            highlight_changes: true,
+            event_count: 8,
+            events: VecDeque::new(),
+            prev: vec![],
+        }
+    }
+}
+
+/// An imgui window, as found in the draw data.
+struct WindowInfo {
+    name: String,
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub(crate) fn record_events(&mut self, events: &[Event]) {
+        for event in events {
+            self.events.push_back(format!("{:?}", event));
+        }
+        while self.events.len() > self.event_count {
+            self.events.pop_front();
+        }
+    }
+
+    /// Draws the overlay into `cells`, a freshly rasterized `nx` by `ny`
+    /// screen.
+    pub(crate) fn draw(&mut self, draw_data: &imgui::DrawData, cells: &mut [sys::ImTui::TCell], nx: usize, ny: usize,
+                       stats: &FrameHistory) {
+        if !self.visible {
+            self.prev.clear();
+            return;
+        }
+
+        if self.highlight_changes {
+            if self.prev.len() == cells.len() {
+                let mut prev = std::mem::replace(&mut self.prev, vec![]);
+                for (cell, old) in cells.iter_mut().zip(prev.iter_mut()) {
+                    let changed = *cell != *old;
+                    *old = *cell;
+                    if changed {
+                        *cell = (*cell & 0x00FF_FFFF) | ((CHANGED_BG as u32) << 24);
+                    }
+                }
+                self.prev = prev;
+            } else {
+                self.prev = cells.to_vec();
+            }
+        }
+
+        let mut lines = vec![];
+        let last = stats.last().cloned().unwrap_or_default();
+        lines.push(timings("last", &last));
+        lines.push(timings("avg", &stats.average()));
+        lines.push(format!(
+            "cmds {} vtx {} changed {} out {}B",
+            last.draw_commands, last.vertices, last.changed_cells, last.output_bytes
+        ));
+        let mouse = unsafe { (*imgui::sys::igGetIO()).MousePos };
+        let mouse = if mouse.x >= 0.0 && mouse.y >= 0.0 {
+            format!("{},{}", mouse.x as i32, mouse.y as i32)
+        } else {
+            "-".to_string()
+        };
+        lines.push(format!("screen {}x{}  mouse {}", nx, ny, mouse));
+        lines.push("windows:".to_string());
+        for window in windows(draw_data) {
+            lines.push(format!(
+                " {} [{},{} {}x{}]",
+                window.name,
+                window.min[0] as i32,
+                window.min[1] as i32,
+                (window.max[0] - window.min[0]) as i32,
+                (window.max[1] - window.min[1]) as i32
+            ));
+        }
+        lines.push("events:".to_string());
+        for event in &self.events {
+            lines.push(format!(" {}", event));
+        }
+
+        let width = PANEL_WIDTH.min(nx);
+        let left = nx - width;
+        put(cells, nx, ny, left, 0, width, " imtui debug", TITLE_COLORS);
+        for (i, line) in lines.iter().enumerate() {
+            put(cells, nx, ny, left, i + 1, width, line, PANEL_COLORS);
+        }
+    }
+}
+
+fn timings(label: &str, stats: &FrameStats) -> String {
+    let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+    format!(
+        "{} in {:.1} ui {:.1} rast {:.1} out {:.1} ms",
+        label,
+        ms(stats.input),
+        ms(stats.build),
+        ms(stats.rasterize),
+        ms(stats.flush)
+    )
+}
+
+/// Lists the windows that drew something, with the bounds of what they drew.
+fn windows(draw_data: &imgui::DrawData) -> Vec<WindowInfo> {
+    let mut windows = vec![];
+    unsafe {
+        let raw = draw_data.raw();
+        if raw.CmdLists.is_null() {
+            return windows;
+        }
+        for i in 0..raw.CmdListsCount.max(0) as usize {
+            let list = &**raw.CmdLists.add(i);
+            if list.VtxBuffer.Size <= 0 || list.VtxBuffer.Data.is_null() {
+                continue;
+            }
+            let verts = std::slice::from_raw_parts(list.VtxBuffer.Data, list.VtxBuffer.Size as usize);
+            let mut min = [std::f32::MAX, std::f32::MAX];
+            let mut max = [std::f32::MIN, std::f32::MIN];
+            for vert in verts {
+                min[0] = min[0].min(vert.pos.x);
+                min[1] = min[1].min(vert.pos.y);
+                max[0] = max[0].max(vert.pos.x);
+                max[1] = max[1].max(vert.pos.y);
+            }
+            let name = if list._OwnerName.is_null() {
+                "?".to_string()
+            } else {
+                CStr::from_ptr(list._OwnerName).to_string_lossy().into_owned()
+            };
+            windows.push(WindowInfo { name, min, max });
+        }
+    }
+    windows
+}
+
+/// Writes `text` at column `x` of row `y`, padded or cut to `width` cells.
+fn put(cells: &mut [sys::ImTui::TCell], nx: usize, ny: usize, x: usize, y: usize, width: usize, text: &str,
+       (fg, bg): (u8, u8)) {
+    if y >= ny {
+        return;
+    }
+    let mut chars = text.chars();
+    for column in x..(x + width).min(nx) {
+        let c = chars.next().unwrap_or(' ');
+        let ch = if (c as u32) < 0x10000 { c as u32 } else { '?' as u32 };
+        cells[y * nx + column] = ch | ((fg as u32) << 16) | ((bg as u32) << 24);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::Cell;
+    use crate::test_util;
+
+    fn row(cells: &[sys::ImTui::TCell], nx: usize, y: usize) -> String {
+        cells[y * nx..(y + 1) * nx].iter().map(|&cell| Cell::from(cell).to_char()).collect()
+    }
+
+    /// Draws a visible overlay over `cells` with empty draw data.
+    fn draw(overlay: &mut DebugOverlay, cells: &mut [sys::ImTui::TCell], nx: usize, ny: usize) {
+        let raw: imgui::sys::ImDrawData = unsafe { std::mem::zeroed() };
+        let draw_data = unsafe { imgui::DrawData::from_raw(&raw) };
+        overlay.draw(draw_data, cells, nx, ny, &FrameHistory::default());
+    }
+
+    #[test]
+    fn pads_and_cuts_lines_to_the_panel() {
+        let mut cells = vec![0; 8 * 2];
+        put(&mut cells, 8, 2, 2, 0, 4, "abcdef", PANEL_COLORS);
+        put(&mut cells, 8, 2, 5, 1, 4, "xy", PANEL_COLORS);
+        put(&mut cells, 8, 2, 0, 2, 4, "below", PANEL_COLORS);
+        assert_eq!(row(&cells, 8, 0), "  abcd  ");
+        assert_eq!(row(&cells, 8, 1), "     xy ");
+        assert_eq!(Cell::from(cells[2]).fg, PANEL_COLORS.0);
+        assert_eq!(Cell::from(cells[2]).bg, PANEL_COLORS.1);
+        assert!(cells[..2].iter().all(|&cell| cell == 0));
+    }
+
+    #[test]
+    fn fits_small_screens() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut overlay = DebugOverlay {
+            visible: true,
+            ..DebugOverlay::default()
+        };
+
+        let mut cells = vec![0; 10 * 3];
+        draw(&mut overlay, &mut cells, 10, 3);
+        assert_eq!(row(&cells, 10, 0), " imtui deb");
+        assert!(row(&cells, 10, 1).starts_with("last in"));
+
+        // On wider screens the panel is on the right.
+        let mut cells = vec![0; 60 * 2];
+        draw(&mut overlay, &mut cells, 60, 2);
+        assert_eq!(row(&cells, 60, 0)[..14].trim(), "");
+        assert!(row(&cells, 60, 0)[14..].starts_with(" imtui debug"));
+    }
+
+    #[test]
+    fn tints_changed_cells() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut overlay = DebugOverlay {
+            visible: true,
+            ..DebugOverlay::default()
+        };
+
+        let blank = b'.' as sys::ImTui::TCell;
+        let mut cells = vec![blank; 60 * 2];
+        draw(&mut overlay, &mut cells, 60, 2);
+        assert_eq!(Cell::from(cells[0]).bg, 0);
+
+        let mut cells = vec![blank; 60 * 2];
+        cells[1] = b'x' as sys::ImTui::TCell;
+        draw(&mut overlay, &mut cells, 60, 2);
+        assert_eq!(Cell::from(cells[0]).bg, 0);
+        assert_eq!(Cell::from(cells[1]), Cell { ch: b'x' as u16, fg: 0, bg: CHANGED_BG });
+    }
+
+    #[test]
+    fn draws_nothing_while_hidden() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = imgui::Context::create();
+        let mut overlay = DebugOverlay::default();
+        let mut cells = vec![0; 60 * 2];
+        draw(&mut overlay, &mut cells, 60, 2);
+        assert!(cells.iter().all(|&cell| cell == 0));
+    }
+
+    #[test]
+    fn keeps_the_last_events() {
+        let mut overlay = DebugOverlay {
+            event_count: 2,
+            ..DebugOverlay::default()
+        };
+        overlay.record_events(&[Event::FocusGained, Event::FocusLost, Event::FocusGained]);
+        assert_eq!(overlay.events, vec!["FocusLost", "FocusGained"]);
+    }
+}
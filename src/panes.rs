@@ -0,0 +1,486 @@
+//! Panes side by side or stacked, with dividers that can be dragged with the
+//! mouse or moved from the keyboard.
+
+use crate::input::{Event, Key, KeyEvent, MouseButton, MouseEvent, MouseKind};
+use crate::layout::{Constraint, Direction, Layout, Rect};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// A change to the panes made by `SplitPanes::handle_events`, which the app
+/// mirrors in its list of pane contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaneChange {
+    /// A pane was inserted at this index.
+    Added(usize),
+    /// The pane at this index was removed.
+    Closed(usize),
+    Swapped(usize, usize),
+    /// The pane at this index got the focus.
+    Focused(usize),
+    /// A divider moved.
+    Resized,
+}
+
+/// A row or column of panes split by one-cell dividers.
+///
+/// Panes keep their share of the space as the area is resized. The
+/// direction, sizes and focus are serializable, so an app can save them with
+/// `Settings` and restore the layout the user left. `min_size`, `max_panes`
+/// and `step` are the app's to set, and start at their defaults when
+/// deserialized.
+///
+/// `handle_events` applies these keys to the focused pane, with the arrows
+/// along the split's direction:
+///
+/// - Alt+Right/Down grows it and Alt+Left/Up shrinks it,
+/// - Alt+Shift+arrows swap it with its neighbour,
+/// - Alt+o focuses the next pane,
+/// - Alt++ adds a pane after it, and Alt+w closes it.
+///
+/// Dragging a divider with the left button resizes the panes on either
+/// side, and clicking a pane focuses it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SavedPanes", into = "SavedPanes")]
+pub struct SplitPanes {
+    pub direction: Direction,
+    /// The relative size of each pane, never empty.
+    weights: Vec<f32>,
+    focused: usize,
+    /// Panes are never resized below this many cells.
+    pub min_size: u16,
+    /// Adding panes stops here.
+    pub max_panes: usize,
+    /// Cells a pane grows or shrinks by per key press.
+    pub step: u16,
+    rects: Vec<Rect>,
+    area: Rect,
+    /// The divider being dragged, which is after the pane with its index.
+    drag: Option<usize>,
+}
+
+/// The part of `SplitPanes` that is saved.
+#[derive(Serialize, Deserialize)]
+struct SavedPanes {
+    direction: Direction,
+    weights: Vec<f32>,
+    focused: usize,
+}
+
+impl TryFrom<SavedPanes> for SplitPanes {
+    type Error = String;
+
+    fn try_from(saved: SavedPanes) -> Result<SplitPanes, String> {
+        if saved.weights.is_empty() {
+            return Err("no panes".to_string());
+        }
+        if saved.weights.iter().any(|w| !w.is_finite() || *w <= 0.0) {
+            return Err(format!("bad pane sizes {:?}", saved.weights));
+        }
+        let mut panes = SplitPanes::new(saved.direction, saved.weights.len());
+        panes.weights = saved.weights;
+        panes.focus(saved.focused);
+        Ok(panes)
+    }
+}
+
+impl From<SplitPanes> for SavedPanes {
+    fn from(panes: SplitPanes) -> SavedPanes {
+        SavedPanes {
+            direction: panes.direction,
+            weights: panes.weights,
+            focused: panes.focused,
+        }
+    }
+}
+
+impl SplitPanes {
+    /// `count` panes of the same size.
+    pub fn new(direction: Direction, count: usize) -> SplitPanes {
+        SplitPanes {
+            direction,
+            weights: vec![1.0; count.max(1)],
+            focused: 0,
+            min_size: 4,
+            max_panes: 8,
+            step: 2,
+            rects: vec![],
+            area: Rect::default(),
+            drag: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn focus(&mut self, index: usize) {
+        self.focused = index.min(self.len().saturating_sub(1));
+    }
+
+    /// Splits `area` into one rectangle per pane. Call it every frame; the
+    /// rectangles are also what mouse events are tested against.
+    pub fn split(&mut self, area: Rect) -> Vec<Rect> {
+        self.focused = self.focused.min(self.len() - 1);
+        let length = self.length(area);
+        let gaps = (self.len() as u32 - 1).min(length as u32) as u16;
+        let available = (length - gaps) as f32;
+        let total: f32 = self.weights.iter().sum();
+
+        // Round down, then hand the remaining cells to the panes that lost
+        // the most.
+        let exact: Vec<f32> = self.weights.iter().map(|w| available * w / total.max(std::f32::EPSILON)).collect();
+        let mut sizes: Vec<u16> = exact.iter().map(|e| e.floor() as u16).collect();
+        let mut spare = (available as u16).saturating_sub(sizes.iter().sum());
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by(|&a, &b| (exact[b] - exact[b].floor()).partial_cmp(&(exact[a] - exact[a].floor())).unwrap());
+        for i in order {
+            if spare == 0 {
+                break;
+            }
+            sizes[i] += 1;
+            spare -= 1;
+        }
+
+        let constraints = sizes.into_iter().map(Constraint::Fixed).collect();
+        self.rects = Layout::new(self.direction, constraints).gap(1).split(area);
+        self.area = area;
+        self.rects.clone()
+    }
+
+    /// Inserts a pane after the focused one, half its size, and focuses it.
+    /// Returns its index, or `None` at `max_panes`.
+    pub fn add(&mut self) -> Option<usize> {
+        if self.len() >= self.max_panes {
+            return None;
+        }
+        let index = self.focused + 1;
+        let weight = self.weights[self.focused] / 2.0;
+        self.weights[self.focused] -= weight;
+        self.weights.insert(index, weight.max(std::f32::EPSILON));
+        self.rects.clear();
+        self.focused = index;
+        Some(index)
+    }
+
+    /// Removes a pane, giving its space to a neighbour. The last pane can't
+    /// be closed.
+    pub fn close(&mut self, index: usize) -> bool {
+        if self.len() <= 1 || index >= self.len() {
+            return false;
+        }
+        let weight = self.weights.remove(index);
+        let neighbour = if index > 0 { index - 1 } else { 0 };
+        self.weights[neighbour] += weight;
+        self.rects.clear();
+        if self.focused >= index && self.focused > 0 {
+            self.focused -= 1;
+        }
+        true
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if a >= self.len() || b >= self.len() {
+            return;
+        }
+        self.weights.swap(a, b);
+        self.rects.clear();
+        if self.focused == a {
+            self.focused = b;
+        } else if self.focused == b {
+            self.focused = a;
+        }
+    }
+
+    /// Grows a pane by `cells` at the expense of its next neighbour, or its
+    /// previous one for the last pane.
+    pub fn grow(&mut self, index: usize, cells: u16) {
+        self.resize(index, cells as i32);
+    }
+
+    pub fn shrink(&mut self, index: usize, cells: u16) {
+        self.resize(index, -(cells as i32));
+    }
+
+    /// Moves up to `delta` cells from the neighbour to pane `index`, or back
+    /// for a negative `delta`, without taking either below `min_size`. A pane
+    /// that is already smaller, e.g. in a small area, only gives up nothing.
+    /// Before the first `split` there are no cells to move.
+    fn resize(&mut self, index: usize, delta: i32) {
+        if self.len() < 2 || index >= self.len() || self.length(self.area) == 0 {
+            return;
+        }
+        let neighbour = if index + 1 < self.len() { index + 1 } else { index - 1 };
+        let mut sizes = self.sizes();
+        // Saved weights must stay positive, so no pane goes below one cell.
+        let min = self.min_size.max(1) as f32;
+        if sizes.iter().any(|&size| size < 1.0) {
+            return;
+        }
+        let delta = (delta as f32).min((sizes[neighbour] - min).max(0.0)).max(-(sizes[index] - min).max(0.0));
+        if delta == 0.0 {
+            return;
+        }
+        sizes[index] += delta;
+        sizes[neighbour] -= delta;
+        self.weights = sizes;
+        self.rects.clear();
+    }
+
+    /// Moves the divider after pane `index` to `position`, a column or row
+    /// of the area.
+    fn move_divider(&mut self, index: usize, position: u16) {
+        if index + 1 >= self.rects.len() {
+            return;
+        }
+        let start = self.start(self.rects[index]);
+        let current = self.length(self.rects[index]);
+        let target = position.saturating_sub(start);
+        self.resize(index, target as i32 - current as i32);
+    }
+
+    /// The size of each pane in cells as last split, or as the area would be
+    /// shared after panes were added or closed since.
+    fn sizes(&self) -> Vec<f32> {
+        if self.rects.len() == self.len() {
+            self.rects.iter().map(|rect| self.length(*rect) as f32).collect()
+        } else {
+            let total: f32 = self.weights.iter().sum();
+            let available = self.length(self.area) as f32;
+            self.weights.iter().map(|w| available * w / total).collect()
+        }
+    }
+
+    fn length(&self, rect: Rect) -> u16 {
+        match self.direction {
+            Direction::Horizontal => rect.width,
+            Direction::Vertical => rect.height,
+        }
+    }
+
+    fn start(&self, rect: Rect) -> u16 {
+        match self.direction {
+            Direction::Horizontal => rect.x,
+            Direction::Vertical => rect.y,
+        }
+    }
+
+    /// The divider at a mouse position, if any.
+    fn divider_at(&self, mouse: &MouseEvent) -> Option<usize> {
+        if !self.area.contains(mouse.x, mouse.y) {
+            return None;
+        }
+        let position = match self.direction {
+            Direction::Horizontal => mouse.x,
+            Direction::Vertical => mouse.y,
+        };
+        self.rects.windows(2).position(|pair| {
+            let end = self.start(pair[0]) + self.length(pair[0]);
+            position >= end && position < self.start(pair[1]).max(end + 1)
+        })
+    }
+
+    /// Applies the key bindings and mouse drags above to the panes, and
+    /// returns what changed.
+    pub fn handle_events(&mut self, events: &[Event]) -> Vec<PaneChange> {
+        let mut changes = vec![];
+        for event in events {
+            match event {
+                Event::Key(key) => self.handle_key(key, &mut changes),
+                Event::Mouse(mouse) => self.handle_mouse(mouse, &mut changes),
+                _ => {}
+            }
+        }
+        changes
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent, changes: &mut Vec<PaneChange>) {
+        if !key.modifiers.alt || key.modifiers.ctrl {
+            return;
+        }
+        let (back, forward) = match self.direction {
+            Direction::Horizontal => (Key::Left, Key::Right),
+            Direction::Vertical => (Key::Up, Key::Down),
+        };
+        let focused = self.focused;
+        match key.key {
+            k if k == forward && key.modifiers.shift => {
+                if focused + 1 < self.len() {
+                    self.swap(focused, focused + 1);
+                    changes.push(PaneChange::Swapped(focused, focused + 1));
+                }
+            }
+            k if k == back && key.modifiers.shift => {
+                if focused > 0 {
+                    self.swap(focused, focused - 1);
+                    changes.push(PaneChange::Swapped(focused, focused - 1));
+                }
+            }
+            k if k == forward => {
+                self.grow(focused, self.step);
+                changes.push(PaneChange::Resized);
+            }
+            k if k == back => {
+                self.shrink(focused, self.step);
+                changes.push(PaneChange::Resized);
+            }
+            Key::Char('o') => {
+                self.focused = (focused + 1) % self.len();
+                changes.push(PaneChange::Focused(self.focused));
+            }
+            Key::Char('+') | Key::Char('=') => {
+                if let Some(index) = self.add() {
+                    changes.push(PaneChange::Added(index));
+                    changes.push(PaneChange::Focused(index));
+                }
+            }
+            Key::Char('w') => {
+                if self.close(focused) {
+                    changes.push(PaneChange::Closed(focused));
+                    changes.push(PaneChange::Focused(self.focused));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: &MouseEvent, changes: &mut Vec<PaneChange>) {
+        match mouse.kind {
+            MouseKind::Press(MouseButton::Left) => {
+                if let Some(divider) = self.divider_at(mouse) {
+                    self.drag = Some(divider);
+                } else if let Some(index) = self.rects.iter().position(|rect| rect.contains(mouse.x, mouse.y)) {
+                    if index != self.focused {
+                        self.focused = index;
+                        changes.push(PaneChange::Focused(index));
+                    }
+                }
+            }
+            MouseKind::Drag(MouseButton::Left) => {
+                if let Some(divider) = self.drag {
+                    let position = match self.direction {
+                        Direction::Horizontal => mouse.x,
+                        Direction::Vertical => mouse.y,
+                    };
+                    self.move_divider(divider, position);
+                    // Keep the rectangles current for the next drag event.
+                    let area = self.area;
+                    self.split(area);
+                    changes.push(PaneChange::Resized);
+                }
+            }
+            MouseKind::Release(_) => self.drag = None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widths(rects: &[Rect]) -> Vec<u16> {
+        rects.iter().map(|rect| rect.width).collect()
+    }
+
+    #[test]
+    fn resizing_stops_at_min_size() {
+        let area = Rect::new(0, 0, 21, 5);
+        let mut panes = SplitPanes::new(Direction::Horizontal, 2);
+        panes.min_size = 4;
+        assert_eq!(widths(&panes.split(area)), vec![10, 10]);
+
+        panes.grow(0, 8);
+        assert_eq!(widths(&panes.split(area)), vec![16, 4]);
+        panes.grow(0, 2);
+        assert_eq!(widths(&panes.split(area)), vec![16, 4]);
+
+        panes.shrink(0, 20);
+        assert_eq!(widths(&panes.split(area)), vec![4, 16]);
+
+        // The last pane takes from the one before it.
+        panes.grow(1, 20);
+        assert_eq!(widths(&panes.split(area)), vec![4, 16]);
+        panes.shrink(1, 3);
+        assert_eq!(widths(&panes.split(area)), vec![7, 13]);
+    }
+
+    #[test]
+    fn resizing_never_moves_cells_out_of_undersized_panes() {
+        let area = Rect::new(0, 0, 21, 5);
+        let mut panes = SplitPanes::new(Direction::Horizontal, 2);
+        panes.min_size = 12;
+        assert_eq!(widths(&panes.split(area)), vec![10, 10]);
+
+        // Both panes are below min_size, so neither has cells to give.
+        panes.shrink(0, 1);
+        assert_eq!(widths(&panes.split(area)), vec![10, 10]);
+        panes.grow(0, 1);
+        assert_eq!(widths(&panes.split(area)), vec![10, 10]);
+
+        panes.min_size = 8;
+        panes.grow(0, 5);
+        assert_eq!(widths(&panes.split(area)), vec![12, 8]);
+
+        // The last pane is now undersized: it can grow but not shrink.
+        panes.min_size = 10;
+        panes.shrink(1, 1);
+        assert_eq!(widths(&panes.split(area)), vec![12, 8]);
+        panes.grow(1, 5);
+        assert_eq!(widths(&panes.split(area)), vec![10, 10]);
+    }
+
+    #[test]
+    fn resizing_waits_for_the_first_split() {
+        let mut panes = SplitPanes::new(Direction::Vertical, 3);
+        panes.min_size = 12;
+        panes.grow(0, 1);
+        panes.shrink(2, 1);
+        assert_eq!(panes.weights, vec![1.0, 1.0, 1.0]);
+
+        let json = serde_json::to_string(&panes).unwrap();
+        let loaded: SplitPanes = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.len(), 3);
+    }
+
+    #[test]
+    fn resizing_keeps_every_pane_at_least_a_cell() {
+        let area = Rect::new(0, 0, 9, 5);
+        let mut panes = SplitPanes::new(Direction::Horizontal, 2);
+        panes.min_size = 0;
+        assert_eq!(widths(&panes.split(area)), vec![4, 4]);
+        panes.grow(0, 10);
+        assert_eq!(widths(&panes.split(area)), vec![7, 1]);
+        assert!(serde_json::from_str::<SplitPanes>(&serde_json::to_string(&panes).unwrap()).is_ok());
+
+        // Too small to give every pane a cell.
+        let tiny = Rect::new(0, 0, 2, 5);
+        assert_eq!(widths(&panes.split(tiny)), vec![1, 0]);
+        panes.grow(1, 1);
+        assert!(panes.weights.iter().all(|&w| w > 0.0));
+    }
+
+    #[test]
+    fn saves_only_the_layout() {
+        let mut panes = SplitPanes::new(Direction::Vertical, 3);
+        panes.min_size = 12;
+        panes.focus(2);
+        let json = serde_json::to_string(&panes).unwrap();
+        assert!(!json.contains("min_size") && !json.contains("max_panes") && !json.contains("step"));
+
+        let loaded: SplitPanes = serde_json::from_str(&json).unwrap();
+        assert_eq!((loaded.direction, loaded.len(), loaded.focused()), (Direction::Vertical, 3, 2));
+        assert_eq!(loaded.min_size, SplitPanes::new(Direction::Vertical, 1).min_size);
+    }
+
+    #[test]
+    fn rejects_saved_panes_without_sizes() {
+        let empty = r#"{"direction":"Horizontal","weights":[],"focused":0}"#;
+        assert!(serde_json::from_str::<SplitPanes>(empty).is_err());
+        let negative = r#"{"direction":"Horizontal","weights":[1.0,-1.0],"focused":0}"#;
+        assert!(serde_json::from_str::<SplitPanes>(negative).is_err());
+    }
+}
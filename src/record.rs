@@ -0,0 +1,236 @@
+//! Recording sessions as asciicast v2 files, which `asciinema play` and the
+//! asciinema web player can replay.
+
+use crate::ansi::AnsiOutput;
+use crate::color::ResolvedColors;
+use crate::cursor::CursorShape;
+use crate::sys;
+use serde::Serialize;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Writes every frame, and optionally every input, to an asciicast v2 file.
+///
+/// Frames are recorded as the escape sequences that draw them on a terminal
+/// of the frame's size, whichever backend displays them. Set a recorder on a
+/// backend with `set_recorder`, and call `finish` once it is taken back off.
+pub struct Recorder {
+    writer: Box<dyn Write>,
+    start: Instant,
+    output: AnsiOutput,
+    out: Vec<u8>,
+    size: Option<(u16, u16)>,
+    record_input: bool,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    /// Records to the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        Ok(Recorder::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Records to `writer`. The header is written with the first frame, once
+    /// the terminal size is known.
+    pub fn new<W: Write + 'static>(writer: W) -> Recorder {
+        Recorder {
+            writer: Box::new(writer),
+            start: Instant::now(),
+            output: AnsiOutput::new(),
+            out: vec![],
+            size: None,
+            record_input: false,
+            error: None,
+        }
+    }
+
+    /// Also records the input read from the terminal, as "i" events.
+    pub fn with_input(mut self, record_input: bool) -> Recorder {
+        self.record_input = record_input;
+        self
+    }
+
+    /// Flushes the recording, and returns the first error writing it hit.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.writer.flush()
+    }
+
+    pub(crate) fn input(&mut self, bytes: &[u8]) {
+        if self.record_input && self.size.is_some() && !bytes.is_empty() {
+            let data = String::from_utf8_lossy(bytes).into_owned();
+            self.event("i", &data);
+        }
+    }
+
+    pub(crate) fn frame(&mut self, screen: &sys::ImTui::TScreen, colors: &ResolvedColors, caret: Option<(i32, i32)>,
+                        cursor_shape: CursorShape) {
+        let size = (screen.nx.max(0) as u16, screen.ny.max(0) as u16);
+        self.out.clear();
+        match self.size {
+            None => {
+                self.header(size);
+                self.out.extend_from_slice(b"\x1b[?25l\x1b[2J");
+            }
+            Some(old) if old != size => {
+                self.event("r", &format!("{}x{}", size.0, size.1));
+                self.output.invalidate();
+                self.out.extend_from_slice(b"\x1b[2J");
+            }
+            Some(_) => {}
+        }
+        self.size = Some(size);
+
+        let mut out = std::mem::replace(&mut self.out, vec![]);
+        self.output.draw(screen, 0, colors, caret, cursor_shape, &mut out);
+        if !out.is_empty() {
+            let data = String::from_utf8_lossy(&out).into_owned();
+            self.event("o", &data);
+        }
+        self.out = out;
+    }
+
+    fn header(&mut self, (width, height): (u16, u16)) {
+        let header = Header {
+            version: 2,
+            width,
+            height,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            env: env::var("TERM").ok().map(|term| HeaderEnv { term }),
+        };
+        self.write_line(&header);
+    }
+
+    /// Writes an event with the time since the recording started, in
+    /// microseconds like asciinema itself.
+    fn event(&mut self, kind: &str, data: &str) {
+        let time = (self.start.elapsed().as_secs_f64() * 1e6).round() / 1e6;
+        self.write_line(&(time, kind, data));
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) {
+        let mut line = serde_json::to_vec(value).expect("asciicast lines are always valid JSON");
+        line.push(b'\n');
+        self.write(&line);
+    }
+
+    /// Writes to the recording. After the first error, nothing more is
+    /// written and `finish` reports it.
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.writer.write_all(bytes) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// The first line of an asciicast v2 file.
+#[derive(Serialize)]
+struct Header {
+    version: u32,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env: Option<HeaderEnv>,
+}
+
+#[derive(Serialize)]
+struct HeaderEnv {
+    #[serde(rename = "TERM")]
+    term: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use sys::ImTui::{TCell, TScreen};
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn screen(cells: &mut Vec<TCell>, nx: i32, ny: i32) -> TScreen {
+        cells.resize((nx * ny) as usize, 0);
+        TScreen {
+            nx,
+            ny,
+            nmax: nx * ny,
+            data: cells.as_mut_ptr(),
+        }
+    }
+
+    fn lines(buf: &Rc<RefCell<Vec<u8>>>) -> Vec<Value> {
+        let text = String::from_utf8(buf.borrow().clone()).unwrap();
+        text.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn writes_a_header_and_events_as_json() {
+        let buf = Rc::new(RefCell::new(vec![]));
+        let mut recorder = Recorder::new(Shared(buf.clone())).with_input(true);
+        // Nothing is written before the first frame gives the size.
+        recorder.input(b"x");
+        assert!(buf.borrow().is_empty());
+
+        let mut cells = vec![b'h' as TCell | 15 << 16, b'i' as TCell | 15 << 16];
+        recorder.frame(&screen(&mut cells, 3, 2), &ResolvedColors::default(), None, CursorShape::Default);
+        recorder.input(b"\"q\\\x1b\n");
+        recorder.finish().unwrap();
+
+        let lines = lines(&buf);
+        assert_eq!(lines.len(), 3);
+        let header = &lines[0];
+        assert_eq!(header["version"], 2);
+        assert_eq!((header["width"].as_u64(), header["height"].as_u64()), (Some(3), Some(2)));
+        assert!(header["timestamp"].is_u64());
+        if let Ok(term) = env::var("TERM") {
+            assert_eq!(header["env"]["TERM"], term.as_str());
+        }
+
+        let output = &lines[1];
+        assert!(output[0].is_f64());
+        assert_eq!(output[1], "o");
+        assert!(output[2].as_str().unwrap().contains("hi"));
+
+        let input = &lines[2];
+        assert_eq!(input[1], "i");
+        assert_eq!(input[2], "\"q\\\u{1b}\n");
+        assert!(input[0].as_f64() >= output[0].as_f64());
+    }
+
+    #[test]
+    fn records_resizes() {
+        let buf = Rc::new(RefCell::new(vec![]));
+        let mut recorder = Recorder::new(Shared(buf.clone()));
+        let mut cells = vec![];
+        recorder.frame(&screen(&mut cells, 3, 2), &ResolvedColors::default(), None, CursorShape::Default);
+        // Unchanged frames write nothing.
+        recorder.frame(&screen(&mut cells, 3, 2), &ResolvedColors::default(), None, CursorShape::Default);
+        recorder.frame(&screen(&mut cells, 4, 2), &ResolvedColors::default(), None, CursorShape::Default);
+        recorder.finish().unwrap();
+
+        let lines = lines(&buf);
+        let kinds: Vec<&str> = lines[1..].iter().map(|line| line[1].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["o", "r", "o"]);
+        assert_eq!(lines[2][2], "4x2");
+    }
+}
@@ -0,0 +1,244 @@
+//! Capturing the input of a session, and replaying it frame by frame with
+//! the same timing.
+//!
+//! A capture starts with `MAGIC`, followed by one record per frame: the time
+//! since the previous frame in microseconds as a little-endian `u32`, the
+//! display columns and rows and the terminal row of the top of the display
+//! as `u16`s, and the length of the frame's raw terminal input as a `u32`,
+//! followed by the input.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const MAGIC: &[u8; 8] = b"IMTUIIN1";
+/// The most input a frame can have, so a corrupt length can't make reading a
+/// capture allocate gigabytes.
+const MAX_FRAME_INPUT: usize = 16 << 20;
+
+/// The input of one frame.
+pub(crate) struct ReplayFrame {
+    pub delta: Duration,
+    pub size: (u16, u16),
+    /// Terminal row of the top of the display, which mouse reports in the
+    /// input are relative to.
+    pub origin: u16,
+    pub input: Vec<u8>,
+}
+
+/// Writes the input and timing of every frame to a capture file.
+///
+/// Set it on a backend with `set_input_capture`, and call `finish` once it
+/// is taken back off.
+pub struct InputCapture {
+    writer: Box<dyn Write>,
+    error: Option<io::Error>,
+}
+
+impl InputCapture {
+    /// Captures to the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<InputCapture> {
+        InputCapture::new(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn new<W: Write + 'static>(mut writer: W) -> io::Result<InputCapture> {
+        writer.write_all(MAGIC)?;
+        Ok(InputCapture {
+            writer: Box::new(writer),
+            error: None,
+        })
+    }
+
+    /// Flushes the capture, and returns the first error writing it hit.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.writer.flush()
+    }
+
+    pub(crate) fn frame(&mut self, delta: Duration, size: (u16, u16), origin: u16, input: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        if input.len() > MAX_FRAME_INPUT {
+            self.error = Some(io::Error::new(io::ErrorKind::InvalidInput, "frame input too large to capture"));
+            return;
+        }
+        let micros = delta.as_micros().min(u32::max_value() as u128) as u32;
+        let mut record = Vec::with_capacity(14 + input.len());
+        record.extend_from_slice(&micros.to_le_bytes());
+        record.extend_from_slice(&size.0.to_le_bytes());
+        record.extend_from_slice(&size.1.to_le_bytes());
+        record.extend_from_slice(&origin.to_le_bytes());
+        record.extend_from_slice(&(input.len() as u32).to_le_bytes());
+        record.extend_from_slice(input);
+        if let Err(err) = self.writer.write_all(&record) {
+            self.error = Some(err);
+        }
+    }
+}
+
+/// Frames read back from a capture.
+///
+/// While a backend has a replay set, each `new_frame` takes its input, its
+/// display size and the time since the last frame from the next captured
+/// frame instead of the terminal and the system clock. Live input is ignored
+/// until the replay runs out.
+pub struct InputReplay {
+    frames: Vec<ReplayFrame>,
+    next: usize,
+}
+
+impl InputReplay {
+    /// Reads the capture at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<InputReplay> {
+        InputReplay::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<InputReplay> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an imtui input capture"));
+        }
+
+        let mut frames = vec![];
+        let mut header = [0u8; 14];
+        while read_header(&mut reader, &mut header)? {
+            let micros = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let nx = u16::from_le_bytes([header[4], header[5]]);
+            let ny = u16::from_le_bytes([header[6], header[7]]);
+            let origin = u16::from_le_bytes([header[8], header[9]]);
+            let len = u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as usize;
+            if len > MAX_FRAME_INPUT {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "frame input too large"));
+            }
+            let mut input = vec![0u8; len];
+            reader.read_exact(&mut input)?;
+            frames.push(ReplayFrame {
+                delta: Duration::from_micros(micros as u64),
+                size: (nx, ny),
+                origin,
+                input,
+            });
+        }
+        Ok(InputReplay { frames, next: 0 })
+    }
+
+    /// The number of captured frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The number of frames that haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.frames.len() - self.next
+    }
+
+    pub(crate) fn next_frame(&mut self) -> Option<ReplayFrame> {
+        let frame = self.frames.get_mut(self.next)?;
+        self.next += 1;
+        Some(ReplayFrame {
+            delta: frame.delta,
+            size: frame.size,
+            origin: frame.origin,
+            input: std::mem::replace(&mut frame.input, vec![]),
+        })
+    }
+}
+
+/// Fills `header` with the next frame record's header. Returns false at the
+/// end of the capture, and an error if it ends partway through a header.
+fn read_header<R: Read>(reader: &mut R, header: &mut [u8; 14]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated input capture")),
+            Ok(n) => filled += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture(frames: &[(u64, (u16, u16), u16, &[u8])]) -> Vec<u8> {
+        let buf = Rc::new(RefCell::new(vec![]));
+        let mut capture = InputCapture::new(Shared(buf.clone())).unwrap();
+        for &(millis, size, origin, input) in frames {
+            capture.frame(Duration::from_millis(millis), size, origin, input);
+        }
+        capture.finish().unwrap();
+        Rc::try_unwrap(buf).unwrap().into_inner()
+    }
+
+    #[test]
+    fn replays_what_was_captured() {
+        let bytes = capture(&[(16, (80, 24), 0, b""), (33, (80, 24), 0, b"ab"), (16, (40, 10), 3, b"\x1b")]);
+        let mut replay = InputReplay::from_reader(&bytes[..]).unwrap();
+        assert_eq!((replay.len(), replay.remaining()), (3, 3));
+
+        let frame = replay.next_frame().unwrap();
+        assert_eq!((frame.delta, frame.size, frame.origin), (Duration::from_millis(16), (80, 24), 0));
+        assert!(frame.input.is_empty());
+        assert_eq!(replay.next_frame().unwrap().input, b"ab".to_vec());
+        let frame = replay.next_frame().unwrap();
+        assert_eq!((frame.size, frame.origin, frame.input), ((40, 10), 3, b"\x1b".to_vec()));
+        assert!(replay.next_frame().is_none());
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let err = InputReplay::from_reader(&b"IMTUIIN2"[..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_huge_frames() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0; 10]);
+        bytes.extend_from_slice(&u32::max_value().to_le_bytes());
+        let err = InputReplay::from_reader(&bytes[..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_captures() {
+        let bytes = capture(&[(16, (80, 24), 0, b"ab"), (16, (80, 24), 0, b"cd")]);
+        // Every cut inside the second record, its header or its input.
+        let second = MAGIC.len() + 14 + 2;
+        for len in second + 1..bytes.len() {
+            let err = InputReplay::from_reader(&bytes[..len]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "cut at {}", len);
+        }
+        assert_eq!(InputReplay::from_reader(&bytes[..second]).unwrap().len(), 1);
+        assert_eq!(InputReplay::from_reader(&bytes[..]).unwrap().len(), 2);
+    }
+}
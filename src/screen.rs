@@ -0,0 +1,93 @@
+use crate::sys;
+use std;
+
+/// A single decoded screen cell.
+///
+/// `imtui` packs a cell into 32 bits: the character in the low 16 bits, the
+/// foreground palette index in the next 8 and the background in the top 8.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub ch: u16,
+    pub fg: u8,
+    pub bg: u8,
+}
+
+impl Cell {
+    /// True for cells that were cleared and never drawn to.
+    pub fn is_blank(&self) -> bool {
+        self.ch == 0 && self.fg == 0 && self.bg == 0
+    }
+
+    /// The character to display, with empty cells shown as a space.
+    pub fn to_char(&self) -> char {
+        match self.ch {
+            0 => ' ',
+            c => std::char::from_u32(c as u32).unwrap_or(' '),
+        }
+    }
+}
+
+impl From<sys::ImTui::TCell> for Cell {
+    fn from(cell: sys::ImTui::TCell) -> Cell {
+        Cell {
+            ch: (cell & 0x0000FFFF) as u16,
+            fg: ((cell & 0x00FF0000) >> 16) as u8,
+            bg: ((cell & 0xFF000000) >> 24) as u8,
+        }
+    }
+}
+
+/// Returns the raw cells of a screen in row-major order.
+pub(crate) fn cells(screen: &sys::ImTui::TScreen) -> &[sys::ImTui::TCell] {
+    if screen.data.is_null() || screen.nx <= 0 || screen.ny <= 0 {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(screen.data, (screen.nx * screen.ny) as usize) }
+}
+
+/// A `TScreen` whose cells are owned by Rust.
+///
+/// `ImTui_ImplText_RenderDrawData` resizes the screen to the display size and
+/// only reallocates once that no longer fits in `nmax` cells. Growing the
+/// buffer to the display size beforehand keeps the C++ side from ever
+/// allocating it.
+pub(crate) struct Screen {
+    raw: sys::ImTui::TScreen,
+    cells: Vec<sys::ImTui::TCell>,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen {
+            raw: sys::ImTui::TScreen {
+                nx: 0,
+                ny: 0,
+                nmax: 0,
+                data: std::ptr::null_mut(),
+            },
+            cells: vec![],
+        }
+    }
+
+    /// Makes room for a `nx` by `ny` display and returns the screen to
+    /// render it into.
+    pub fn prepare(&mut self, nx: i32, ny: i32) -> *mut sys::ImTui::TScreen {
+        let size = (nx.max(0) * ny.max(0)) as usize;
+        if self.cells.len() < size {
+            self.cells.resize(size, 0);
+        }
+        self.raw.nmax = self.cells.len() as i32;
+        self.raw.data = self.cells.as_mut_ptr();
+        &mut self.raw
+    }
+
+    pub fn raw(&self) -> &sys::ImTui::TScreen {
+        &self.raw
+    }
+
+    /// The cells of the last rendered frame, for drawing on top of it.
+    pub fn cells_mut(&mut self) -> &mut [sys::ImTui::TCell] {
+        let size = (self.raw.nx.max(0) * self.raw.ny.max(0)) as usize;
+        &mut self.cells[..size.min(self.cells.len())]
+    }
+}
@@ -0,0 +1,533 @@
+//! Keeping a UI running while viewers attach and detach, like tmux.
+//!
+//! A `Server` runs in the app's process and listens on a Unix socket. Each
+//! viewer runs `attach`, which puts its terminal in raw mode, forwards input
+//! and resizes to the server, and shows what the server draws. The app keeps
+//! its state across detaches, and several viewers can watch at once.
+//!
+//! Viewers send messages of a kind byte, a big-endian `u16` length and a
+//! payload. The server answers with plain terminal output.
+
+use crate::ansi::{self, AnsiOutput};
+use crate::backend::Backend;
+use crate::frontend::Frontend;
+use crate::input::Parser;
+use crate::tty::{self, Tty, TtySource};
+use imgui;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Sent once on attach: the access byte, then the columns and rows.
+const HELLO: u8 = 0;
+/// Input read from the viewer's terminal.
+const INPUT: u8 = 1;
+/// The new columns and rows of the viewer's terminal.
+const RESIZE: u8 = 2;
+
+/// The size used until the first viewer attaches.
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+/// A viewer whose unsent output grows past this many bytes is dropped.
+const MAX_QUEUED: usize = 1 << 20;
+
+/// What an attached viewer may do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Only watch. Input from the viewer is ignored.
+    ReadOnly,
+    /// Watch and send input to the app.
+    ReadWrite,
+}
+
+/// An attached viewer, as seen by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewer {
+    pub access: Access,
+    /// The size of the viewer's terminal in columns and rows.
+    pub size: (u16, u16),
+}
+
+/// A viewer's connection to the server.
+struct Connection {
+    stream: UnixStream,
+    incoming: Vec<u8>,
+    parser: Parser,
+    output: AnsiOutput,
+    /// Output the viewer hasn't accepted yet.
+    out: Vec<u8>,
+    /// Set while the viewer hasn't taken all of the last frame, so frames
+    /// are skipped until it catches up.
+    behind: bool,
+    /// Set once the viewer said hello.
+    viewer: Option<Viewer>,
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: UnixStream) -> Connection {
+        Connection {
+            stream,
+            incoming: vec![],
+            parser: Parser::new(),
+            output: AnsiOutput::new(),
+            out: vec![],
+            behind: false,
+            viewer: None,
+            closed: false,
+        }
+    }
+
+    /// Reads everything the viewer sent so far.
+    fn receive(&mut self) {
+        let mut chunk = [0u8; 4096];
+        while !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(n) => self.incoming.extend_from_slice(&chunk[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    /// Writes as much of the queued output as the viewer takes without
+    /// blocking.
+    fn send(&mut self) {
+        while !self.closed && !self.out.is_empty() {
+            match self.stream.write(&self.out) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.out.drain(..n);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => self.closed = true,
+            }
+        }
+        self.behind = !self.out.is_empty();
+        if self.out.len() > MAX_QUEUED {
+            self.closed = true;
+        }
+    }
+
+    /// Repaints the viewer's terminal from scratch on the next frame.
+    fn clear(&mut self) {
+        self.output.invalidate();
+        self.out.extend_from_slice(b"\x1b[2J");
+    }
+}
+
+/// Runs the UI for whoever is attached over a Unix socket.
+///
+/// The app is drawn at the size of the smallest attached terminal, so every
+/// viewer sees all of it. Without viewers, frames are still drawn, at the
+/// last size, and the app keeps running.
+pub struct Server {
+    frontend: Frontend,
+    listener: UnixListener,
+    path: PathBuf,
+    connections: Vec<Connection>,
+    mouse_support: bool,
+    size: (u16, u16),
+}
+
+impl Server {
+    /// Listens on the socket at `path`. A stale socket left behind by a
+    /// server that is gone is replaced; one that is still served is an
+    /// `AddrInUse` error.
+    pub fn bind<P: AsRef<Path>>(path: P, mouse_support: bool, active_fps: f32, idle_fps: f32) -> io::Result<Server> {
+        let path = path.as_ref();
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, "a session is already served on this socket"));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            frontend: Frontend::new(active_fps, idle_fps),
+            listener,
+            path: path.to_path_buf(),
+            connections: vec![],
+            mouse_support,
+            size: DEFAULT_SIZE,
+        })
+    }
+
+    /// The path of the socket viewers attach to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The viewers attached as of the last call to `new_frame`.
+    pub fn viewers(&self) -> Vec<Viewer> {
+        self.connections.iter().filter_map(|c| c.viewer).collect()
+    }
+
+    /// Returns the size the app is drawn at, in columns and rows.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Accepts new viewers, reads what the attached ones sent and feeds the
+    /// input of read-write viewers to imgui. Returns true if there was any.
+    pub fn new_frame(&mut self) -> bool {
+        self.frontend.stats.begin_input();
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.connections.push(Connection::new(stream));
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+
+        let mut events = vec![];
+        let mut had_input = false;
+        let now = Instant::now();
+        for connection in self.connections.iter_mut() {
+            connection.receive();
+            while let Some((kind, payload)) = take_message(&mut connection.incoming) {
+                match kind {
+                    HELLO if payload.len() == 5 && connection.viewer.is_none() => {
+                        let access = if payload[0] == 0 { Access::ReadWrite } else { Access::ReadOnly };
+                        connection.viewer = Some(Viewer {
+                            access,
+                            size: read_size(&payload[1..]),
+                        });
+                        connection.clear();
+                        let mouse_support = self.mouse_support && access == Access::ReadWrite;
+                        connection.out.extend_from_slice(&ansi::enable_modes(mouse_support));
+                    }
+                    RESIZE if payload.len() == 4 => {
+                        if let Some(viewer) = connection.viewer.as_mut() {
+                            viewer.size = read_size(&payload);
+                            connection.clear();
+                        }
+                    }
+                    INPUT => {
+                        if let Some(Viewer { access: Access::ReadWrite, .. }) = connection.viewer {
+                            had_input |= !payload.is_empty();
+                            self.frontend.record_input(&payload);
+                            connection.parser.feed(&payload, now, &mut events);
+                        }
+                    }
+                    // Messages this server doesn't know are skipped.
+                    _ => {}
+                }
+            }
+            connection.parser.flush(now, &mut events);
+        }
+        self.connections.retain(|c| !c.closed);
+
+        let size = self
+            .connections
+            .iter()
+            .filter_map(|c| c.viewer.map(|v| v.size))
+            .fold(None, |min: Option<(u16, u16)>, (nx, ny)| match min {
+                Some((mx, my)) => Some((mx.min(nx), my.min(ny))),
+                None => Some((nx, ny)),
+            })
+            .unwrap_or(self.size);
+        if size != self.size {
+            self.size = size;
+            for connection in self.connections.iter_mut().filter(|c| c.viewer.is_some()) {
+                connection.clear();
+            }
+        }
+
+        self.frontend.new_frame_with_events(events, had_input, self.size, 0);
+        had_input
+    }
+
+    /// Draws the frame and sends it to every attached viewer, without
+    /// waiting for any of them. Viewers that haven't taken the last frame
+    /// yet skip this one, and those that stop reading are dropped.
+    pub fn render(&mut self, draw_data: &imgui::DrawData) {
+        let mut fds: Vec<RawFd> = self.connections.iter().map(|c| c.stream.as_raw_fd()).collect();
+        fds.push(self.listener.as_raw_fd());
+        let escape = self.connections.iter().filter_map(|c| c.parser.escape_deadline()).min();
+        self.frontend.rasterize(draw_data, || {
+            tty::poll_readable(&fds, 0).iter().any(|&ready| ready)
+                || escape.map_or(false, |deadline| Instant::now() >= deadline)
+        });
+
+        let colors = self.frontend.colors();
+        let caret = self.frontend.caret();
+        let cursor_shape = self.frontend.cursor_shape;
+        // Copies go to the viewers that could have made them.
+        let mut clipboard = vec![];
+        self.frontend.take_clipboard_output(&mut clipboard);
+        let screen = self.frontend.screen();
+        let (mut changed_cells, mut output_bytes) = (0, 0);
+        for connection in self.connections.iter_mut().filter(|c| c.viewer.is_some()) {
+            let before = connection.out.len();
+            if let Some(Viewer { access: Access::ReadWrite, .. }) = connection.viewer {
+                connection.out.extend_from_slice(&clipboard);
+            }
+            // The painter compares with what was queued, so a skipped frame
+            // is made up for by the next one drawn.
+            if !connection.behind {
+                let changed = connection.output.draw(screen, 0, &colors, caret, cursor_shape, &mut connection.out);
+                changed_cells = changed_cells.max(changed);
+            }
+            output_bytes += connection.out.len() - before;
+            connection.send();
+        }
+        self.connections.retain(|c| !c.closed);
+        self.frontend.stats.current.changed_cells = changed_cells;
+        self.frontend.stats.current.output_bytes = output_bytes;
+        self.frontend.stats.end_frame();
+    }
+}
+
+impl Backend for Server {
+    fn frontend(&self) -> &Frontend {
+        &self.frontend
+    }
+
+    fn frontend_mut(&mut self) -> &mut Frontend {
+        &mut self.frontend
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// How `attach` connects a terminal to a session.
+#[derive(Clone, Debug)]
+pub struct AttachOptions {
+    pub tty: TtySource,
+    pub access: Access,
+    /// Typing this byte detaches. Defaults to Ctrl-\.
+    pub detach_key: u8,
+}
+
+impl Default for AttachOptions {
+    fn default() -> AttachOptions {
+        AttachOptions {
+            tty: TtySource::Stdio,
+            access: Access::ReadWrite,
+            detach_key: 0x1c,
+        }
+    }
+}
+
+/// Shows the session served on the socket at `path` until the detach key is
+/// typed or the server goes away. The terminal is restored either way.
+pub fn attach<P: AsRef<Path>>(path: P, options: &AttachOptions) -> io::Result<()> {
+    let mut stream = UnixStream::connect(path)?;
+    let fds = options.tty.open()?;
+    let tty = Tty::new(fds.input, fds.output)?;
+    tty.write_all(b"\x1b[?1049h")?;
+    let result = forward(&mut stream, &tty, fds.input, options);
+
+    let mut restore = ansi::disable_modes(true);
+    restore.extend_from_slice(b"\x1b[?1049l");
+    let _ = tty.write_all(&restore);
+    result
+}
+
+/// Passes input and resizes from the terminal to the server, and output the
+/// other way.
+fn forward(stream: &mut UnixStream, tty: &Tty, input_fd: RawFd, options: &AttachOptions) -> io::Result<()> {
+    let mut size = tty.size()?;
+    let mut hello = vec![if options.access == Access::ReadWrite { 0 } else { 1 }];
+    write_size(&mut hello, size);
+    write_message(stream, HELLO, &hello)?;
+
+    let socket_fd = stream.as_raw_fd();
+    let mut chunk = [0u8; 4096];
+    let mut input = vec![];
+    loop {
+        let ready = tty::poll_readable(&[socket_fd, input_fd], 100);
+        if ready[0] {
+            match stream.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(n) => tty.write_all(&chunk[..n])?,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if ready[1] {
+            input.clear();
+            tty.read_input(&mut input)?;
+            let detach = input.iter().position(|&b| b == options.detach_key);
+            if let Some(end) = detach {
+                input.truncate(end);
+            }
+            if options.access == Access::ReadWrite {
+                for part in input.chunks(u16::max_value() as usize) {
+                    write_message(stream, INPUT, part)?;
+                }
+            }
+            if detach.is_some() {
+                return Ok(());
+            }
+        }
+        if let Ok(new_size) = tty.size() {
+            if new_size != size {
+                size = new_size;
+                let mut payload = vec![];
+                write_size(&mut payload, size);
+                write_message(stream, RESIZE, &payload)?;
+            }
+        }
+    }
+}
+
+fn write_message<W: Write>(writer: &mut W, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(3 + payload.len());
+    message.push(kind);
+    message.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    message.extend_from_slice(payload);
+    writer.write_all(&message)
+}
+
+/// Removes the first complete message from `buf` and returns its kind and
+/// payload.
+fn take_message(buf: &mut Vec<u8>) -> Option<(u8, Vec<u8>)> {
+    if buf.len() < 3 {
+        return None;
+    }
+    let len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+    if buf.len() < 3 + len {
+        return None;
+    }
+    let kind = buf[0];
+    let payload = buf[3..3 + len].to_vec();
+    buf.drain(..3 + len);
+    Some((kind, payload))
+}
+
+fn write_size(out: &mut Vec<u8>, (nx, ny): (u16, u16)) {
+    out.extend_from_slice(&nx.to_be_bytes());
+    out.extend_from_slice(&ny.to_be_bytes());
+}
+
+fn read_size(bytes: &[u8]) -> (u16, u16) {
+    let nx = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let ny = u16::from_be_bytes([bytes[2], bytes[3]]);
+    (nx.max(1), ny.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Event, Key, KeyEvent};
+    use crate::test_util;
+    use std::time::Duration;
+
+    fn attach_raw(path: &Path, access: Access, size: (u16, u16)) -> UnixStream {
+        let mut stream = UnixStream::connect(path).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+        let mut hello = vec![if access == Access::ReadWrite { 0 } else { 1 }];
+        write_size(&mut hello, size);
+        write_message(&mut stream, HELLO, &hello).unwrap();
+        stream
+    }
+
+    /// What the server sent so far.
+    fn received(stream: &mut UnixStream) -> Vec<u8> {
+        let mut out = vec![];
+        let mut chunk = [0u8; 4096];
+        while let Ok(n) = stream.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..n]);
+        }
+        out
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    fn draw_frame(imgui: &mut imgui::Context, server: &mut Server) {
+        server.new_frame();
+        let ui = imgui.frame();
+        let title = imgui::ImString::new("main");
+        let window = imgui::Window::new(&title)
+            .position([0.0, 0.0], imgui::Condition::Always)
+            .size([20.0, 4.0], imgui::Condition::Always);
+        if let Some(window_token) = window.begin(&ui) {
+            ui.text("served");
+            window_token.end(&ui);
+        }
+        server.render(ui.render());
+    }
+
+    #[test]
+    fn viewers_say_hello_and_get_frames() {
+        let _lock = test_util::imgui_lock();
+        let path = test_util::temp_dir("session-hello").join("socket");
+        let mut imgui = crate::context().build();
+        let mut server = Server::bind(&path, true, 1000.0, 1000.0).unwrap();
+
+        let mut read_only = attach_raw(&path, Access::ReadOnly, (30, 8));
+        let mut read_write = attach_raw(&path, Access::ReadWrite, (24, 6));
+        draw_frame(&mut imgui, &mut server);
+
+        let mut viewers = server.viewers();
+        viewers.sort_by_key(|v| v.size);
+        assert_eq!(
+            viewers,
+            vec![
+                Viewer { access: Access::ReadWrite, size: (24, 6) },
+                Viewer { access: Access::ReadOnly, size: (30, 8) },
+            ]
+        );
+        assert_eq!(server.size(), (24, 6));
+
+        let ro = received(&mut read_only);
+        let rw = received(&mut read_write);
+        assert!(contains(&ro, b"served"));
+        assert!(contains(&rw, b"served"));
+        // Only viewers that send input get mouse reports.
+        assert!(!contains(&ro, b"\x1b[?1006h"));
+        assert!(contains(&rw, b"\x1b[?1006h"));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn only_read_write_input_reaches_the_app() {
+        let _lock = test_util::imgui_lock();
+        let path = test_util::temp_dir("session-input").join("socket");
+        let mut imgui = crate::context().build();
+        let mut server = Server::bind(&path, false, 1000.0, 1000.0).unwrap();
+
+        let mut read_only = attach_raw(&path, Access::ReadOnly, (20, 5));
+        let mut read_write = attach_raw(&path, Access::ReadWrite, (20, 5));
+        write_message(&mut read_only, INPUT, b"r").unwrap();
+        write_message(&mut read_write, INPUT, b"w").unwrap();
+        draw_frame(&mut imgui, &mut server);
+
+        let keys: Vec<Key> = server
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Key(KeyEvent { key, .. }) => Some(*key),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(keys, vec![Key::Char('w')]);
+
+        // Input from a read-only viewer is ignored, not an error.
+        assert_eq!(server.viewers().len(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Keeping imgui's window settings and an app's own state between runs.
+
+use imgui;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const INI_FILE: &str = "imgui.ini";
+const STATE_FILE: &str = "state.toml";
+
+/// imgui's window positions, sizes and collapsed state, and string values
+/// set by the app, stored in `$XDG_STATE_HOME/<app>/`.
+///
+/// Create the context without an ini file, as `imtui::context()` does, and
+/// let `Settings` load and save it instead:
+///
+/// ```ignore
+/// let mut imgui = imtui::context().build();
+/// let mut settings = imtui::Settings::open("myapp")?;
+/// settings.load_imgui(&mut imgui);
+/// loop {
+///     // ... draw a frame ...
+///     settings.autosave(&mut imgui)?;
+/// }
+/// settings.save(&mut imgui)?;
+/// ```
+///
+/// Files are replaced atomically, so a crash while saving leaves the last
+/// saved settings in place.
+pub struct Settings {
+    dir: PathBuf,
+    values: BTreeMap<String, String>,
+    ini: String,
+    changed: bool,
+    last_save: Instant,
+    /// How often `autosave` writes changed settings.
+    pub autosave_interval: Duration,
+}
+
+impl Settings {
+    /// Loads the settings of `app` from `$XDG_STATE_HOME/<app>/`, or
+    /// `~/.local/state/<app>/` when the variable isn't set.
+    pub fn open(app: &str) -> io::Result<Settings> {
+        Settings::open_in(state_home()?.join(app))
+    }
+
+    /// Loads the settings stored in `dir`, which is created on the first
+    /// save. Missing files are empty settings.
+    pub fn open_in<P: Into<PathBuf>>(dir: P) -> io::Result<Settings> {
+        let dir = dir.into();
+        let ini = read_optional(&dir.join(INI_FILE))?;
+        let values = match read_optional(&dir.join(STATE_FILE))? {
+            text if text.is_empty() => BTreeMap::new(),
+            text => toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
+        };
+        Ok(Settings {
+            dir,
+            values,
+            ini,
+            changed: false,
+            last_save: Instant::now(),
+            autosave_interval: Duration::from_secs(5),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Restores imgui's window settings. Call it before the first frame.
+    pub fn load_imgui(&self, imgui: &mut imgui::Context) {
+        if !self.ini.is_empty() {
+            imgui.load_ini_settings(&self.ini);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    /// The value of `key` parsed as a `T`, or `None` if it is missing or
+    /// doesn't parse.
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn set<V: ToString>(&mut self, key: &str, value: V) {
+        let value = value.to_string();
+        if self.values.get(key) != Some(&value) {
+            self.values.insert(key.to_string(), value);
+            self.changed = true;
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if self.values.remove(key).is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Saves the settings if imgui's or the app's changed and the last save
+    /// was at least `autosave_interval` ago. Call it once per frame, after
+    /// `render`. Returns true if it saved.
+    pub fn autosave(&mut self, imgui: &mut imgui::Context) -> io::Result<bool> {
+        if self.last_save.elapsed() < self.autosave_interval {
+            return Ok(false);
+        }
+        if !self.changed && !imgui.io().want_save_ini_settings {
+            return Ok(false);
+        }
+        self.save(imgui)?;
+        Ok(true)
+    }
+
+    /// Saves imgui's window settings and the app's values now, e.g. on
+    /// shutdown.
+    pub fn save(&mut self, imgui: &mut imgui::Context) -> io::Result<()> {
+        let mut ini = String::new();
+        imgui.save_ini_settings(&mut ini);
+        imgui.io_mut().want_save_ini_settings = false;
+        self.last_save = Instant::now();
+
+        fs::create_dir_all(&self.dir)?;
+        if ini != self.ini {
+            write_atomic(&self.dir.join(INI_FILE), ini.as_bytes())?;
+            self.ini = ini;
+        }
+        if self.changed {
+            let text = toml::to_string(&self.values).expect("string values are always valid TOML");
+            write_atomic(&self.dir.join(STATE_FILE), text.as_bytes())?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
+
+fn state_home() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    match env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+        Some(home) => Ok(PathBuf::from(home).join(".local").join("state")),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "neither XDG_STATE_HOME nor HOME is set")),
+    }
+}
+
+fn read_optional(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
+
+/// Writes `data` to a temporary file next to `path` and renames it over
+/// `path`, so readers see either the old or the new contents.
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+    let result = File::create(&tmp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            let _ = fs::remove_file(&tmp);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn writes_atomically() {
+        let dir = test_util::temp_dir("settings-atomic");
+        let path = dir.join("file");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, vec![std::ffi::OsString::from("file")]);
+    }
+
+    #[test]
+    fn saves_and_reloads_values() {
+        let _lock = test_util::imgui_lock();
+        let dir = test_util::temp_dir("settings-reload").join("app");
+        let mut imgui = crate::context().build();
+
+        let mut settings = Settings::open_in(&dir).unwrap();
+        assert_eq!(settings.get("theme"), None);
+        settings.set("theme", "dark");
+        settings.set("count", 3);
+        settings.set("gone", "soon");
+        settings.remove("gone");
+        settings.save(&mut imgui).unwrap();
+
+        let reloaded = Settings::open_in(&dir).unwrap();
+        assert_eq!(reloaded.get("theme"), Some("dark"));
+        assert_eq!(reloaded.get_parsed::<u32>("count"), Some(3));
+        assert_eq!(reloaded.get("gone"), None);
+    }
+
+    #[test]
+    fn rejects_a_broken_state_file() {
+        let dir = test_util::temp_dir("settings-broken");
+        fs::write(dir.join(STATE_FILE), "not = [toml").unwrap();
+        let error = Settings::open_in(&dir).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,241 @@
+//! Timings and counts of the frames a backend drew.
+
+use crate::trace::{FrameSpans, Step};
+use imgui;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How many frames a history keeps by default, two seconds at 60 fps.
+const DEFAULT_CAPACITY: usize = 120;
+
+/// Where the time of one frame went, and how much was drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Reading input and feeding it to imgui, in `new_frame`.
+    pub input: Duration,
+    /// Building the UI, from the end of `new_frame` to `render`.
+    pub build: Duration,
+    /// Rasterizing the draw data into screen cells.
+    pub rasterize: Duration,
+    /// Waiting for the next frame at the frame rate limit.
+    pub wait: Duration,
+    /// Painting the changed cells and writing them to the terminal.
+    pub flush: Duration,
+    pub draw_commands: usize,
+    pub vertices: usize,
+    /// Cells that differ from the previous frame.
+    pub changed_cells: usize,
+    /// Bytes written to the terminal. Not counted by the ncurses backend.
+    pub output_bytes: usize,
+}
+
+impl FrameStats {
+    /// The time of the frame spent doing work, that is without `wait`.
+    pub fn busy(&self) -> Duration {
+        self.input + self.build + self.rasterize + self.flush
+    }
+}
+
+/// The stats of the most recent frames, oldest first.
+#[derive(Clone, Debug)]
+pub struct FrameHistory {
+    frames: VecDeque<FrameStats>,
+    capacity: usize,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> FrameHistory {
+        FrameHistory {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The last frame drawn.
+    pub fn last(&self) -> Option<&FrameStats> {
+        self.frames.back()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FrameStats> {
+        self.frames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Keeps the stats of the last `capacity` frames.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The mean of every field over the frames in the history.
+    pub fn average(&self) -> FrameStats {
+        let n = self.frames.len();
+        if n == 0 {
+            return FrameStats::default();
+        }
+        let mut sum = FrameStats::default();
+        for frame in &self.frames {
+            sum.input += frame.input;
+            sum.build += frame.build;
+            sum.rasterize += frame.rasterize;
+            sum.wait += frame.wait;
+            sum.flush += frame.flush;
+            sum.draw_commands += frame.draw_commands;
+            sum.vertices += frame.vertices;
+            sum.changed_cells += frame.changed_cells;
+            sum.output_bytes += frame.output_bytes;
+        }
+        FrameStats {
+            input: sum.input / n as u32,
+            build: sum.build / n as u32,
+            rasterize: sum.rasterize / n as u32,
+            wait: sum.wait / n as u32,
+            flush: sum.flush / n as u32,
+            draw_commands: sum.draw_commands / n,
+            vertices: sum.vertices / n,
+            changed_cells: sum.changed_cells / n,
+            output_bytes: sum.output_bytes / n,
+        }
+    }
+
+    fn push(&mut self, stats: FrameStats) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats);
+    }
+}
+
+impl Default for FrameHistory {
+    fn default() -> FrameHistory {
+        FrameHistory::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// Measures the steps of a frame as a backend goes through them, and adds the
+/// frame to the history once it is written out. With the `tracing` feature,
+/// every step is also a span.
+pub(crate) struct FrameTimer {
+    pub history: FrameHistory,
+    pub current: FrameStats,
+    mark: Instant,
+    spans: FrameSpans,
+}
+
+impl FrameTimer {
+    pub fn new() -> FrameTimer {
+        FrameTimer {
+            history: FrameHistory::default(),
+            current: FrameStats::default(),
+            mark: Instant::now(),
+            spans: FrameSpans::new(),
+        }
+    }
+
+    /// Returns the time since the last step, and starts the next one.
+    fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.mark);
+        self.mark = now;
+        elapsed
+    }
+
+    pub fn begin_input(&mut self) {
+        self.current = FrameStats::default();
+        self.lap();
+        self.spans.enter(Some(Step::Input));
+    }
+
+    pub fn end_input(&mut self) {
+        self.current.input = self.lap();
+        self.spans.enter(Some(Step::Build));
+    }
+
+    pub fn begin_render(&mut self, draw_data: &imgui::DrawData) {
+        self.current.build = self.lap();
+        self.spans.enter(Some(Step::Rasterize));
+        self.current.vertices = draw_data.total_vtx_count.max(0) as usize;
+        self.current.draw_commands = draw_data.draw_lists().map(|list| list.commands().count()).sum();
+    }
+
+    pub fn rasterized(&mut self) {
+        self.current.rasterize = self.lap();
+        self.spans.enter(Some(Step::Wait));
+    }
+
+    pub fn waited(&mut self) {
+        self.current.wait = self.lap();
+        self.spans.enter(Some(Step::Flush));
+    }
+
+    pub fn end_frame(&mut self) {
+        self.current.flush = self.lap();
+        self.spans.enter(None);
+        self.history.push(self.current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ms: u64, vertices: usize) -> FrameStats {
+        FrameStats {
+            input: Duration::from_millis(ms),
+            build: Duration::from_millis(2 * ms),
+            wait: Duration::from_millis(10),
+            vertices,
+            ..FrameStats::default()
+        }
+    }
+
+    #[test]
+    fn averages_every_field() {
+        let mut history = FrameHistory::new(4);
+        assert_eq!(history.average(), FrameStats::default());
+        history.push(frame(1, 10));
+        history.push(frame(3, 21));
+        let average = history.average();
+        assert_eq!(average.input, Duration::from_millis(2));
+        assert_eq!(average.build, Duration::from_millis(4));
+        assert_eq!(average.wait, Duration::from_millis(10));
+        assert_eq!(average.vertices, 15);
+        assert_eq!(average.busy(), Duration::from_millis(6));
+    }
+
+    #[test]
+    fn keeps_the_last_frames_up_to_the_capacity() {
+        let mut history = FrameHistory::new(3);
+        for ms in 1..=5 {
+            history.push(frame(ms, 0));
+        }
+        let kept: Vec<u64> = history.iter().map(|f| f.input.as_millis() as u64).collect();
+        assert_eq!(kept, vec![3, 4, 5]);
+        assert_eq!(history.last().map(|f| f.input), Some(Duration::from_millis(5)));
+
+        history.set_capacity(1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.last().map(|f| f.input), Some(Duration::from_millis(5)));
+
+        history.set_capacity(0);
+        history.push(frame(6, 0));
+        assert!(history.is_empty());
+        assert_eq!(FrameHistory::default().capacity(), DEFAULT_CAPACITY);
+    }
+}
@@ -0,0 +1,229 @@
+//! Running a UI over any byte stream, such as a pty master, a socket or an
+//! SSH channel.
+
+use crate::ansi::{self, AnsiOutput};
+use crate::backend::Backend;
+use crate::frontend::Frontend;
+use imgui;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Input read from the stream by the reader thread, waiting for the next
+/// frame.
+#[derive(Default)]
+struct Incoming {
+    bytes: Vec<u8>,
+    closed: bool,
+}
+
+/// A backend that reads terminal input from `R` and writes ANSI output to
+/// `W`, for a terminal on the other end of a stream.
+///
+/// The stream is expected to behave like a terminal in raw mode, e.g. the
+/// master side of a pty or an SSH channel with a pty requested. Since there
+/// is no device to ask, the terminal size is given by the caller and updated
+/// with `resize`.
+///
+/// Like the other backends, a `Stream` renders the current imgui context.
+/// To serve several streams from one process, give each its own context and
+/// switch between them.
+pub struct Stream<W: Write> {
+    frontend: Frontend,
+    output: AnsiOutput,
+    out: Vec<u8>,
+    writer: W,
+    incoming: Arc<Mutex<Incoming>>,
+    reader: Option<JoinHandle<()>>,
+    /// Makes the reader thread's `read` return, so it can be joined. Without
+    /// it, the thread is detached on drop.
+    shutdown: Option<Box<dyn FnOnce() + Send>>,
+    input_buf: Vec<u8>,
+    mouse_support: bool,
+    size: (u16, u16),
+    clear: bool,
+}
+
+impl<W: Write> Stream<W> {
+    /// Sets up the terminal on the other end of `writer`, and starts a thread
+    /// that reads input from `reader`. The thread ends once `reader` reaches
+    /// the end of the stream or fails.
+    ///
+    /// The thread is blocked in `read` until input arrives, so only a hook
+    /// set with `set_shutdown` can stop it. With a hook, dropping the
+    /// `Stream` calls it and waits for the thread. Without one, the thread is
+    /// left running until `reader` ends by itself, e.g. because the client
+    /// disconnected.
+    pub fn new<R>(reader: R, writer: W, size: (u16, u16), mouse_support: bool, active_fps: f32, idle_fps: f32)
+        -> io::Result<Stream<W>>
+    where
+        R: Read + Send + 'static,
+    {
+        let incoming = Arc::new(Mutex::new(Incoming::default()));
+        let shared = incoming.clone();
+        let reader = thread::Builder::new()
+            .name("imtui-stream".to_string())
+            .spawn(move || read_loop(reader, &shared))?;
+
+        let mut stream = Stream {
+            frontend: Frontend::new(active_fps, idle_fps),
+            output: AnsiOutput::new(),
+            out: vec![],
+            writer,
+            incoming,
+            reader: Some(reader),
+            shutdown: None,
+            input_buf: vec![],
+            mouse_support,
+            size: (size.0.max(1), size.1.max(1)),
+            clear: false,
+        };
+        let mut setup = b"\x1b[?1049h\x1b[2J".to_vec();
+        setup.extend_from_slice(&ansi::enable_modes(mouse_support));
+        stream.writer.write_all(&setup)?;
+        stream.writer.flush()?;
+        Ok(stream)
+    }
+
+    /// Sets what ends the input side of the stream when the `Stream` is
+    /// dropped, after the terminal is restored, e.g. shutting down a socket
+    /// so that the reader thread's `read` returns. Dropping then waits for
+    /// the reader thread, so the hook must make `read` return.
+    pub fn set_shutdown<F: FnOnce() + Send + 'static>(&mut self, shutdown: F) {
+        self.shutdown = Some(Box::new(shutdown));
+    }
+
+    /// Tells the backend that the terminal is now `columns` by `rows`, e.g.
+    /// after a window change request on an SSH channel. The next frame is
+    /// drawn at the new size.
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        let size = (columns.max(1), rows.max(1));
+        if size != self.size {
+            self.size = size;
+            self.clear = true;
+            self.output.invalidate();
+        }
+    }
+
+    /// Returns the size of the terminal in columns and rows.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// True once the input side of the stream has been closed, e.g. because
+    /// the client disconnected.
+    pub fn is_closed(&self) -> bool {
+        let incoming = self.incoming.lock().unwrap();
+        incoming.closed && incoming.bytes.is_empty()
+    }
+
+    /// Takes the input received since the last frame and feeds it to imgui.
+    /// Returns true if there was any input.
+    pub fn new_frame(&mut self) -> bool {
+        self.frontend.stats.begin_input();
+        {
+            let mut incoming = self.incoming.lock().unwrap();
+            self.input_buf.append(&mut incoming.bytes);
+        }
+        self.frontend.new_frame(&self.input_buf, self.size, 0);
+        let had_input = !self.input_buf.is_empty();
+        self.input_buf.clear();
+        had_input
+    }
+
+    /// Draws the frame and writes it to the stream.
+    pub fn render(&mut self, draw_data: &imgui::DrawData) -> io::Result<()> {
+        if self.clear {
+            self.clear = false;
+            self.writer.write_all(b"\x1b[2J")?;
+        }
+        let incoming = &self.incoming;
+        self.out.clear();
+        ansi::render(&mut self.frontend, &mut self.output, draw_data, 0,
+                     || !incoming.lock().unwrap().bytes.is_empty(), &mut self.out);
+        self.frontend.take_clipboard_output(&mut self.out);
+        self.writer.write_all(&self.out)?;
+        let result = self.writer.flush();
+        self.frontend.stats.end_frame();
+        result
+    }
+}
+
+impl<W: Write> Backend for Stream<W> {
+    fn frontend(&self) -> &Frontend {
+        &self.frontend
+    }
+
+    fn frontend_mut(&mut self) -> &mut Frontend {
+        &mut self.frontend
+    }
+}
+
+impl<W: Write> Drop for Stream<W> {
+    fn drop(&mut self) {
+        let mut out = ansi::disable_modes(self.mouse_support);
+        out.extend_from_slice(b"\x1b[?1049l");
+        let _ = self.writer.write_all(&out);
+        let _ = self.writer.flush();
+        // Joining without a shutdown hook would block until the other end
+        // sends input or closes, so the reader is left to end by itself.
+        let reader = self.reader.take();
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown();
+            if let Some(reader) = reader {
+                let _ = reader.join();
+            }
+        }
+    }
+}
+
+fn read_loop<R: Read>(mut reader: R, incoming: &Mutex<Incoming>) {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => incoming.lock().unwrap().bytes.extend_from_slice(&chunk[..n]),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+    incoming.lock().unwrap().closed = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn drops_without_waiting_for_a_blocked_reader() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = crate::context().build();
+        // The peer stays open, so the reader thread never gets any input.
+        let (socket, _peer) = UnixStream::pair().unwrap();
+        let stream = Stream::new(socket, vec![], (20, 5), false, 30.0, 1.0).unwrap();
+        drop(stream);
+    }
+
+    #[test]
+    fn shuts_down_and_joins_the_reader() {
+        let _lock = test_util::imgui_lock();
+        let _imgui = crate::context().build();
+        let (socket, _peer) = UnixStream::pair().unwrap();
+        let control = socket.try_clone().unwrap();
+        let mut stream = Stream::new(socket, vec![], (20, 5), false, 30.0, 1.0).unwrap();
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = called.clone();
+        stream.set_shutdown(move || {
+            flag.store(true, Ordering::SeqCst);
+            let _ = control.shutdown(Shutdown::Both);
+        });
+        let incoming = stream.incoming.clone();
+        drop(stream);
+        assert!(called.load(Ordering::SeqCst));
+        assert!(incoming.lock().unwrap().closed);
+    }
+}
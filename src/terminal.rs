@@ -0,0 +1,252 @@
+use crate::ansi::{self, AnsiOutput};
+use crate::backend::Backend;
+use crate::frontend::Frontend;
+use crate::tty::{Tty, TtyFds, TtySource};
+use imgui;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the terminal to report the cursor position when
+/// setting up an inline viewport.
+const CURSOR_REPORT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The part of the terminal a `Terminal` draws into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Viewport {
+    /// The whole alternate screen, like the `Ncurses` backend.
+    Fullscreen,
+    /// The given number of rows, starting at the row the cursor is on. The
+    /// rest of the terminal, including the scrollback, is left alone.
+    Inline(u16),
+}
+
+#[derive(Clone, Debug)]
+pub struct TerminalOptions {
+    pub tty: TtySource,
+    pub viewport: Viewport,
+    pub mouse_support: bool,
+    pub active_fps: f32,
+    pub idle_fps: f32,
+    /// In an inline viewport, leave the last frame in the terminal on drop
+    /// instead of erasing it.
+    pub keep_final_frame: bool,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> TerminalOptions {
+        TerminalOptions {
+            tty: TtySource::Stdio,
+            viewport: Viewport::Fullscreen,
+            mouse_support: true,
+            active_fps: 60.0,
+            idle_fps: -1.0,
+            keep_final_frame: true,
+        }
+    }
+}
+
+/// A backend that drives a terminal with ANSI escape sequences instead of
+/// ncurses, which lets it draw into part of the screen.
+pub struct Terminal {
+    frontend: Frontend,
+    output: AnsiOutput,
+    out: Vec<u8>,
+    tty: Tty,
+    _fds: TtyFds,
+    options: TerminalOptions,
+    input_buf: Vec<u8>,
+    /// Terminal row of the top of the viewport.
+    origin: u16,
+    size: (u16, u16),
+}
+
+impl Terminal {
+    pub fn init(options: TerminalOptions) -> io::Result<Terminal> {
+        let fds = options.tty.open()?;
+        let tty = Tty::new(fds.input, fds.output)?;
+        let (columns, rows) = tty.size()?;
+        let (viewport, mouse_support) = (options.viewport, options.mouse_support);
+
+        let mut terminal = Terminal {
+            frontend: Frontend::new(options.active_fps, options.idle_fps),
+            output: AnsiOutput::new(),
+            out: vec![],
+            tty,
+            _fds: fds,
+            options,
+            input_buf: vec![],
+            origin: 0,
+            size: (columns, rows),
+        };
+
+        // Without capabilities to tell, OSC 52 reads stay off until the app
+        // enables them.
+        let link = terminal.tty.link();
+        terminal.frontend.set_clipboard_link(Box::new(link), false);
+
+        match viewport {
+            Viewport::Fullscreen => terminal.tty.write_all(b"\x1b[?1049h\x1b[2J")?,
+            Viewport::Inline(height) => {
+                let height = height.max(1).min(rows);
+                // Scroll the terminal as needed to make room below the cursor,
+                // then go back up and find out where the room starts.
+                let mut reserve = b"\r".to_vec();
+                if height > 1 {
+                    reserve.resize(height as usize, b'\n');
+                    let _ = write!(reserve, "\x1b[{}A", height - 1);
+                }
+                terminal.tty.write_all(&reserve)?;
+                terminal.origin = terminal.cursor_row().unwrap_or(rows - height);
+            }
+        }
+
+        terminal.tty.write_all(&ansi::enable_modes(mouse_support))?;
+        Ok(terminal)
+    }
+
+    /// Asks the terminal for the cursor row, which must be in the first
+    /// column. Input that arrives while waiting is kept for the next frame.
+    fn cursor_row(&mut self) -> Option<u16> {
+        self.tty.write_all(b"\x1b[6n").ok()?;
+        let deadline = Instant::now() + CURSOR_REPORT_TIMEOUT;
+        let mut buf = vec![];
+        while Instant::now() < deadline {
+            if !self.tty.poll(10) {
+                continue;
+            }
+            self.tty.read_input(&mut buf).ok()?;
+            if let Some((start, end, row)) = find_cursor_report(&buf) {
+                self.input_buf.extend_from_slice(&buf[..start]);
+                self.input_buf.extend_from_slice(&buf[end..]);
+                return Some(row);
+            }
+        }
+        self.input_buf.extend_from_slice(&buf);
+        None
+    }
+
+    /// Returns the size of the viewport in columns and rows.
+    pub fn viewport_size(&self) -> (u16, u16) {
+        match self.options.viewport {
+            Viewport::Fullscreen => self.size,
+            Viewport::Inline(height) => {
+                let available = self.size.1.saturating_sub(self.origin).max(1);
+                (self.size.0, height.max(1).min(available))
+            }
+        }
+    }
+
+    /// Reads pending input and feeds it to imgui. Returns true if there was
+    /// any input.
+    pub fn new_frame(&mut self) -> bool {
+        self.frontend.stats.begin_input();
+        if let Ok(size) = self.tty.size() {
+            if size != self.size {
+                self.size = size;
+                self.output.invalidate();
+                match self.options.viewport {
+                    Viewport::Fullscreen => {
+                        let _ = self.tty.write_all(b"\x1b[2J");
+                    }
+                    Viewport::Inline(height) => {
+                        // A shorter terminal scrolls the viewport up; keep
+                        // all of it on screen, and clear what the reflow
+                        // left behind.
+                        let height = height.max(1).min(size.1.max(1));
+                        self.origin = self.origin.min(size.1.saturating_sub(height));
+                        let _ = self.tty.write_all(format!("\x1b[{};1H\x1b[J", self.origin + 1).as_bytes());
+                    }
+                }
+            }
+        }
+
+        self.frontend.take_stashed_input(&mut self.input_buf);
+        let _ = self.tty.read_input(&mut self.input_buf);
+        let size = self.viewport_size();
+        self.frontend.new_frame(&self.input_buf, size, self.origin);
+        let had_input = !self.input_buf.is_empty();
+        self.input_buf.clear();
+        had_input
+    }
+
+    pub fn render(&mut self, draw_data: &imgui::DrawData) {
+        let tty = &self.tty;
+        self.out.clear();
+        ansi::render(&mut self.frontend, &mut self.output, draw_data, self.origin, || tty.poll(0), &mut self.out);
+        let _ = tty.write_all(&self.out);
+        self.frontend.stats.end_frame();
+    }
+}
+
+impl Backend for Terminal {
+    fn frontend(&self) -> &Frontend {
+        &self.frontend
+    }
+
+    fn frontend_mut(&mut self) -> &mut Frontend {
+        &mut self.frontend
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut out = ansi::disable_modes(self.options.mouse_support);
+        match self.options.viewport {
+            Viewport::Fullscreen => out.extend_from_slice(b"\x1b[?1049l"),
+            Viewport::Inline(_) => {
+                let (_, ny) = self.viewport_size();
+                if self.options.keep_final_frame {
+                    let _ = write!(out, "\x1b[{};1H\r\n", self.origin + ny);
+                } else {
+                    let _ = write!(out, "\x1b[{};1H\x1b[J", self.origin + 1);
+                }
+            }
+        }
+        let _ = self.tty.write_all(&out);
+    }
+}
+
+/// Finds the reply to a cursor position query sent with the cursor in the
+/// first column, `ESC [ row ; 1 R`, in `buf`. Returns where it starts and
+/// ends, and the row counting from 0.
+///
+/// Modified F3 keys arrive as `ESC [ 1 ; modifiers R`, with modifiers of 2 or
+/// more, so they can't be mistaken for a report in the first column.
+fn find_cursor_report(buf: &[u8]) -> Option<(usize, usize, u16)> {
+    let mut start = 0;
+    while let Some(offset) = buf[start..].windows(2).position(|w| w == b"\x1b[") {
+        let begin = start + offset;
+        let body = &buf[begin + 2..];
+        let len = body.iter().position(|&b| !(b.is_ascii_digit() || b == b';'))?;
+        if body[len] == b'R' {
+            let report = std::str::from_utf8(&body[..len]).ok()?;
+            let mut values = report.split(';');
+            if let (Some(row), Some("1"), None) = (values.next(), values.next(), values.next()) {
+                if let Ok(row) = row.parse::<u16>() {
+                    return Some((begin, begin + 2 + len + 1, row.saturating_sub(1)));
+                }
+            }
+        }
+        start = begin + 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_cursor_reports_between_other_input() {
+        assert_eq!(find_cursor_report(b"\x1b[12;1R"), Some((0, 7, 11)));
+        assert_eq!(find_cursor_report(b"a\x1b[A\x1b[3;1Rb"), Some((4, 10, 2)));
+        assert_eq!(find_cursor_report(b"\x1b[3;1"), None);
+        assert_eq!(find_cursor_report(b"\x1b[3R"), None);
+    }
+
+    #[test]
+    fn skips_modified_f3_keys() {
+        assert_eq!(find_cursor_report(b"\x1b[1;5R"), None);
+        assert_eq!(find_cursor_report(b"\x1b[1;2R\x1b[7;1R"), Some((6, 12, 6)));
+    }
+}
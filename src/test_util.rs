@@ -0,0 +1,23 @@
+//! Helpers shared by the unit tests.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Mutex, MutexGuard};
+
+static IMGUI: Mutex<()> = Mutex::new(());
+
+/// imgui allows only one context at a time, so tests that create one hold
+/// this lock while it lives.
+pub(crate) fn imgui_lock() -> MutexGuard<'static, ()> {
+    IMGUI.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An empty directory for the test called `name`.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("imtui-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
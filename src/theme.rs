@@ -0,0 +1,422 @@
+//! Themes: colors and style metrics that can be loaded from TOML or JSON
+//! files, switched between frames, and reloaded when their file changes.
+//!
+//! A theme file names a base preset and overrides some of its colors, by
+//! their `imgui::StyleColor` names:
+//!
+//! ```toml
+//! name = "solarized"
+//! base = "dark"
+//!
+//! [metrics]
+//! scrollbar_size = 1.0
+//!
+//! [colors]
+//! Text = "#839496"
+//! WindowBg = "#002b36"
+//! ```
+
+use crate::context::StylePreset;
+use imgui;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The style sizes a theme sets, in cells, on top of
+/// `apply_terminal_metrics`. The defaults are what it sets.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metrics {
+    pub window_padding: [f32; 2],
+    pub frame_padding: [f32; 2],
+    pub item_spacing: [f32; 2],
+    pub item_inner_spacing: [f32; 2],
+    pub indent_spacing: f32,
+    pub scrollbar_size: f32,
+    pub window_border_size: f32,
+    pub child_border_size: f32,
+    pub popup_border_size: f32,
+    pub frame_border_size: f32,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics {
+            window_padding: [1.0, 0.0],
+            frame_padding: [1.0, 0.0],
+            item_spacing: [1.0, 0.0],
+            item_inner_spacing: [1.0, 0.0],
+            indent_spacing: 2.0,
+            scrollbar_size: 1.0,
+            window_border_size: 0.0,
+            child_border_size: 0.0,
+            popup_border_size: 0.0,
+            frame_border_size: 0.0,
+        }
+    }
+}
+
+impl Metrics {
+    pub(crate) fn apply(&self, style: &mut imgui::Style) {
+        style.window_padding = self.window_padding;
+        style.frame_padding = self.frame_padding;
+        style.item_spacing = self.item_spacing;
+        style.item_inner_spacing = self.item_inner_spacing;
+        style.indent_spacing = self.indent_spacing;
+        style.scrollbar_size = self.scrollbar_size;
+        style.window_border_size = self.window_border_size;
+        style.child_border_size = self.child_border_size;
+        style.popup_border_size = self.popup_border_size;
+        style.frame_border_size = self.frame_border_size;
+    }
+}
+
+/// Colors and metrics to apply to an `imgui::Style`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// The preset whose colors the theme starts from.
+    pub base: StylePreset,
+    pub metrics: Metrics,
+    /// Colors that replace those of `base`.
+    pub colors: Vec<(imgui::StyleColor, [f32; 4])>,
+}
+
+/// A theme as written in a file.
+#[derive(Serialize, Deserialize)]
+struct ThemeFile {
+    name: String,
+    #[serde(default = "default_base")]
+    base: String,
+    #[serde(default)]
+    metrics: Metrics,
+    #[serde(default)]
+    colors: BTreeMap<String, String>,
+}
+
+fn default_base() -> String {
+    StylePreset::default().name().to_string()
+}
+
+/// The names of the bundled themes, for `Theme::bundled`.
+pub const BUNDLED: [&str; 4] = ["light", "dark", "solarized", "high-contrast"];
+
+impl Theme {
+    /// A theme with the colors of `base` and the default metrics.
+    pub fn new(name: &str, base: StylePreset) -> Theme {
+        Theme {
+            name: name.to_string(),
+            base,
+            metrics: Metrics::default(),
+            colors: vec![],
+        }
+    }
+
+    /// One of the themes named in `BUNDLED`.
+    pub fn bundled(name: &str) -> Option<Theme> {
+        let theme = match name {
+            "light" => Theme::new(name, StylePreset::Light).with_colors(&[
+                (imgui::StyleColor::WindowBg, 0xf4f4f0),
+                (imgui::StyleColor::ChildBg, 0xf4f4f0),
+                (imgui::StyleColor::PopupBg, 0xf4f4f0),
+                (imgui::StyleColor::Text, 0x000000),
+            ]),
+            "dark" => Theme::new(name, StylePreset::Dark).with_colors(&[
+                (imgui::StyleColor::WindowBg, 0x000000),
+                (imgui::StyleColor::ChildBg, 0x000000),
+                (imgui::StyleColor::Text, 0xe4e4e4),
+            ]),
+            "solarized" => Theme::new(name, StylePreset::Dark).with_colors(&[
+                (imgui::StyleColor::Text, 0x839496),
+                (imgui::StyleColor::TextDisabled, 0x586e75),
+                (imgui::StyleColor::WindowBg, 0x002b36),
+                (imgui::StyleColor::ChildBg, 0x002b36),
+                (imgui::StyleColor::PopupBg, 0x073642),
+                (imgui::StyleColor::FrameBg, 0x073642),
+                (imgui::StyleColor::FrameBgHovered, 0x586e75),
+                (imgui::StyleColor::FrameBgActive, 0x657b83),
+                (imgui::StyleColor::TitleBg, 0x073642),
+                (imgui::StyleColor::TitleBgActive, 0x268bd2),
+                (imgui::StyleColor::TitleBgCollapsed, 0x073642),
+                (imgui::StyleColor::Button, 0x073642),
+                (imgui::StyleColor::ButtonHovered, 0x268bd2),
+                (imgui::StyleColor::ButtonActive, 0x2aa198),
+                (imgui::StyleColor::Header, 0x073642),
+                (imgui::StyleColor::HeaderHovered, 0x268bd2),
+                (imgui::StyleColor::HeaderActive, 0x2aa198),
+                (imgui::StyleColor::CheckMark, 0x859900),
+                (imgui::StyleColor::ScrollbarBg, 0x073642),
+                (imgui::StyleColor::ScrollbarGrab, 0x586e75),
+            ]),
+            "high-contrast" => Theme::new(name, StylePreset::Dark).with_colors(&[
+                (imgui::StyleColor::Text, 0xffffff),
+                (imgui::StyleColor::TextDisabled, 0xc0c0c0),
+                (imgui::StyleColor::WindowBg, 0x000000),
+                (imgui::StyleColor::ChildBg, 0x000000),
+                (imgui::StyleColor::PopupBg, 0x000000),
+                (imgui::StyleColor::FrameBg, 0x000080),
+                (imgui::StyleColor::FrameBgHovered, 0x0000ff),
+                (imgui::StyleColor::FrameBgActive, 0x0000ff),
+                (imgui::StyleColor::TitleBg, 0x000080),
+                (imgui::StyleColor::TitleBgActive, 0x0000ff),
+                (imgui::StyleColor::TitleBgCollapsed, 0x000080),
+                (imgui::StyleColor::Button, 0x000080),
+                (imgui::StyleColor::ButtonHovered, 0x0000ff),
+                (imgui::StyleColor::ButtonActive, 0x008000),
+                (imgui::StyleColor::Header, 0x000080),
+                (imgui::StyleColor::HeaderHovered, 0x0000ff),
+                (imgui::StyleColor::HeaderActive, 0x008000),
+                (imgui::StyleColor::CheckMark, 0xffff00),
+                (imgui::StyleColor::TextSelectedBg, 0x0000ff),
+            ]),
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    fn with_colors(mut self, colors: &[(imgui::StyleColor, u32)]) -> Theme {
+        for (style_color, rgb) in colors {
+            let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.0;
+            self.set_color(*style_color, [channel(16), channel(8), channel(0), 1.0]);
+        }
+        self
+    }
+
+    /// Replaces `style_color`, or adds it to the theme's colors.
+    pub fn set_color(&mut self, style_color: imgui::StyleColor, color: [f32; 4]) {
+        match self.colors.iter_mut().find(|(c, _)| *c == style_color) {
+            Some(entry) => entry.1 = color,
+            None => self.colors.push((style_color, color)),
+        }
+    }
+
+    /// Sets the colors and metrics of `style`. Call it between frames, e.g.
+    /// `theme.apply(imgui.style_mut())`, to switch themes at any time.
+    pub fn apply(&self, style: &mut imgui::Style) {
+        self.base.apply(style);
+        self.metrics.apply(style);
+        for (style_color, color) in &self.colors {
+            style[*style_color] = *color;
+        }
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Theme> {
+        let file: ThemeFile = toml::from_str(text).map_err(invalid_data)?;
+        Theme::from_file(file)
+    }
+
+    pub fn from_json(text: &str) -> io::Result<Theme> {
+        let file: ThemeFile = serde_json::from_str(text).map_err(invalid_data)?;
+        Theme::from_file(file)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.to_file()).expect("a theme is always valid TOML")
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_file()).expect("a theme is always valid JSON")
+    }
+
+    /// Reads a theme from a `.toml` or `.json` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Theme> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match extension(path).as_str() {
+            "toml" => Theme::from_toml(&text),
+            "json" => Theme::from_json(&text),
+            _ => Err(unknown_format(path)),
+        }
+    }
+
+    /// Writes the theme to a `.toml` or `.json` file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let text = match extension(path).as_str() {
+            "toml" => self.to_toml(),
+            "json" => self.to_json(),
+            _ => return Err(unknown_format(path)),
+        };
+        fs::write(path, text)
+    }
+
+    fn from_file(file: ThemeFile) -> io::Result<Theme> {
+        let base = StylePreset::from_name(&file.base)
+            .ok_or_else(|| invalid_data(format!("unknown base preset {:?}", file.base)))?;
+        let mut theme = Theme::new(&file.name, base);
+        theme.metrics = file.metrics;
+        for (name, value) in &file.colors {
+            let style_color = style_color_from_name(name)
+                .ok_or_else(|| invalid_data(format!("unknown style color {:?}", name)))?;
+            let color = parse_color(value).ok_or_else(|| invalid_data(format!("bad color {:?} for {}", value, name)))?;
+            theme.set_color(style_color, color);
+        }
+        Ok(theme)
+    }
+
+    fn to_file(&self) -> ThemeFile {
+        ThemeFile {
+            name: self.name.clone(),
+            base: self.base.name().to_string(),
+            metrics: self.metrics,
+            colors: self
+                .colors
+                .iter()
+                .map(|(style_color, color)| (format!("{:?}", style_color), format_color(*color)))
+                .collect(),
+        }
+    }
+}
+
+fn style_color_from_name(name: &str) -> Option<imgui::StyleColor> {
+    imgui::StyleColor::VARIANTS.iter().cloned().find(|c| format!("{:?}", c) == name)
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let hex = value.strip_prefix('#')?;
+    // `from_str_radix` alone would take a sign, as in `#+f+f+f`.
+    if (hex.len() != 6 && hex.len() != 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut color = [1.0; 4];
+    for (i, channel) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()? as f32 / 255.0;
+    }
+    Some(color)
+}
+
+fn format_color(color: [f32; 4]) -> String {
+    let byte = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    let rgb = format!("#{:02x}{:02x}{:02x}", byte(color[0]), byte(color[1]), byte(color[2]));
+    if byte(color[3]) == 255 {
+        rgb
+    } else {
+        format!("{}{:02x}", rgb, byte(color[3]))
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+fn unknown_format(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{}: themes are .toml or .json files", path.display()),
+    )
+}
+
+fn invalid_data<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// Reloads a theme file when it changes.
+///
+/// Call `poll` once per frame; it looks at the file's modification time at
+/// most every `interval`.
+pub struct ThemeWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>,
+    pub interval: Duration,
+}
+
+impl ThemeWatcher {
+    /// Watches `path`. The first `poll` loads it.
+    pub fn new<P: Into<PathBuf>>(path: P) -> ThemeWatcher {
+        ThemeWatcher {
+            path: path.into(),
+            modified: None,
+            last_check: None,
+            interval: Duration::from_millis(500),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The theme, if the file was written since the last call. A file that
+    /// fails to load is reported once, and tried again when it next changes.
+    pub fn poll(&mut self) -> Option<io::Result<Theme>> {
+        if let Some(last_check) = self.last_check {
+            if last_check.elapsed() < self.interval {
+                return None;
+            }
+        }
+        self.last_check = Some(Instant::now());
+
+        let modified = match fs::metadata(&self.path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(error) => {
+                // Editors often replace the file, so it can be missing for a
+                // moment; report it only if it was never loaded.
+                return if self.modified.is_none() {
+                    self.modified = Some(SystemTime::UNIX_EPOCH);
+                    Some(Err(error))
+                } else {
+                    None
+                };
+            }
+        };
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(Theme::load(&self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_color("#ff8000"), Some([1.0, 128.0 / 255.0, 0.0, 1.0]));
+        assert_eq!(parse_color("#FF800080"), Some([1.0, 128.0 / 255.0, 0.0, 128.0 / 255.0]));
+        assert_eq!(parse_color("ff8000"), None);
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("#gg8000"), None);
+        assert_eq!(parse_color("#ff800"), None);
+        assert_eq!(parse_color("#+f+f+f"), None);
+        assert_eq!(parse_color("#-1ff00"), None);
+    }
+
+    #[test]
+    fn formats_colors_back_to_hex() {
+        assert_eq!(format_color([1.0, 128.0 / 255.0, 0.0, 1.0]), "#ff8000");
+        assert_eq!(format_color([1.0, 128.0 / 255.0, 0.0, 128.0 / 255.0]), "#ff800080");
+    }
+
+    fn sorted_colors(theme: &Theme) -> Vec<(String, [f32; 4])> {
+        let mut colors: Vec<_> = theme.colors.iter().map(|(c, color)| (format!("{:?}", c), *color)).collect();
+        colors.sort_by(|a, b| a.0.cmp(&b.0));
+        colors
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        for name in BUNDLED.iter() {
+            let mut theme = Theme::bundled(name).unwrap();
+            theme.metrics.scrollbar_size = 2.0;
+            let loaded = Theme::from_toml(&theme.to_toml()).unwrap();
+            assert_eq!(loaded.name, theme.name);
+            assert_eq!(loaded.base, theme.base);
+            assert_eq!(loaded.metrics, theme.metrics);
+            assert_eq!(sorted_colors(&loaded), sorted_colors(&theme));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_colors() {
+        let error = Theme::from_toml("name = \"x\"\n[colors]\nNotAColor = \"#000000\"\n").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = Theme::from_toml("name = \"x\"\n[colors]\nText = \"black\"\n").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
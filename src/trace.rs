@@ -0,0 +1,366 @@
+//! `tracing` spans around the steps of every frame, and a layer that keeps
+//! recent spans for a live view in the UI. Enabled with the `tracing`
+//! feature.
+//!
+//! Every backend enters a span for each step of a frame: `new_frame`,
+//! `build` for the app's UI code, `rasterize`, `wait` and `flush`. They have
+//! the target `imtui` and a `frame` field with the frame number.
+
+#[cfg(feature = "tracing")]
+pub use self::layer::{SpanHistory, SpanLayer, SpanRecord, SpanView};
+
+/// A step of a frame, as measured by `FrameTimer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    Input,
+    Build,
+    Rasterize,
+    Wait,
+    Flush,
+}
+
+/// Enters a span for the current step of a frame, and exits it when the
+/// next step starts.
+pub(crate) struct FrameSpans {
+    #[cfg(feature = "tracing")]
+    frame: u64,
+    #[cfg(feature = "tracing")]
+    entered: Option<tracing_crate::span::EnteredSpan>,
+}
+
+impl FrameSpans {
+    pub fn new() -> FrameSpans {
+        FrameSpans {
+            #[cfg(feature = "tracing")]
+            frame: 0,
+            #[cfg(feature = "tracing")]
+            entered: None,
+        }
+    }
+
+    /// Exits the span of the previous step, if any, and enters the span of
+    /// `step`. `None` ends the frame.
+    #[cfg(feature = "tracing")]
+    pub fn enter(&mut self, step: Option<Step>) {
+        use tracing_crate::debug_span;
+
+        self.entered = None;
+        if step == Some(Step::Input) {
+            self.frame += 1;
+        }
+        let frame = self.frame;
+        let span = match step {
+            Some(Step::Input) => debug_span!(target: "imtui", "new_frame", frame),
+            Some(Step::Build) => debug_span!(target: "imtui", "build", frame),
+            Some(Step::Rasterize) => debug_span!(target: "imtui", "rasterize", frame),
+            Some(Step::Wait) => debug_span!(target: "imtui", "wait", frame),
+            Some(Step::Flush) => debug_span!(target: "imtui", "flush", frame),
+            None => return,
+        };
+        self.entered = Some(span.entered());
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn enter(&mut self, _step: Option<Step>) {}
+}
+
+#[cfg(feature = "tracing")]
+mod layer {
+    use std::collections::{HashMap, VecDeque};
+    use std::fmt::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tracing_crate::field::{Field, Visit};
+    use tracing_crate::span::{Attributes, Id};
+    use tracing_crate::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// A closed span.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct SpanRecord {
+        pub name: &'static str,
+        pub target: &'static str,
+        /// The `frame` field, which imtui's own spans have.
+        pub frame: Option<u64>,
+        /// The other fields, formatted as `name=value`.
+        pub fields: String,
+        /// Time from creating the span to closing it.
+        pub duration: Duration,
+    }
+
+    struct Ring {
+        spans: VecDeque<SpanRecord>,
+        capacity: usize,
+    }
+
+    /// The spans most recently closed, oldest first. Clones share the
+    /// history.
+    #[derive(Clone)]
+    pub struct SpanHistory {
+        ring: Arc<Mutex<Ring>>,
+    }
+
+    impl SpanHistory {
+        pub fn new(capacity: usize) -> SpanHistory {
+            SpanHistory {
+                ring: Arc::new(Mutex::new(Ring {
+                    spans: VecDeque::with_capacity(capacity),
+                    capacity,
+                })),
+            }
+        }
+
+        /// A copy of the spans in the history.
+        pub fn spans(&self) -> Vec<SpanRecord> {
+            self.ring.lock().unwrap().spans.iter().cloned().collect()
+        }
+
+        pub fn clear(&self) {
+            self.ring.lock().unwrap().spans.clear();
+        }
+
+        fn push(&self, span: SpanRecord) {
+            let mut ring = self.ring.lock().unwrap();
+            if ring.capacity == 0 {
+                return;
+            }
+            if ring.spans.len() == ring.capacity {
+                ring.spans.pop_front();
+            }
+            ring.spans.push_back(span);
+        }
+    }
+
+    /// What the layer keeps in a span's extensions while it is open.
+    struct OpenSpan {
+        start: Instant,
+        frame: Option<u64>,
+        fields: String,
+    }
+
+    #[derive(Default)]
+    struct FieldVisitor {
+        frame: Option<u64>,
+        fields: String,
+    }
+
+    impl Visit for FieldVisitor {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "frame" {
+                self.frame = Some(value);
+            } else {
+                self.record_debug(field, &value);
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={:?}", field.name(), value);
+        }
+    }
+
+    /// A `tracing_subscriber` layer that adds every closed span to a
+    /// `SpanHistory`.
+    pub struct SpanLayer {
+        history: SpanHistory,
+    }
+
+    impl SpanLayer {
+        /// Returns the layer, and the history to show with a `SpanView`.
+        pub fn new(capacity: usize) -> (SpanLayer, SpanHistory) {
+            let history = SpanHistory::new(capacity);
+            (
+                SpanLayer {
+                    history: history.clone(),
+                },
+                history,
+            )
+        }
+    }
+
+    impl<S> Layer<S> for SpanLayer
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(OpenSpan {
+                    start: Instant::now(),
+                    frame: visitor.frame,
+                    fields: visitor.fields,
+                });
+            }
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let span = match ctx.span(&id) {
+                Some(span) => span,
+                None => return,
+            };
+            let extensions = span.extensions();
+            if let Some(open) = extensions.get::<OpenSpan>() {
+                self.history.push(SpanRecord {
+                    name: span.metadata().name(),
+                    target: span.metadata().target(),
+                    frame: open.frame,
+                    fields: open.fields.clone(),
+                    duration: open.start.elapsed(),
+                });
+            }
+        }
+    }
+
+    /// The durations of every kind of span, by target and name.
+    #[derive(Debug, PartialEq)]
+    struct SpanTotal {
+        target: &'static str,
+        name: &'static str,
+        sum: Duration,
+        max: Duration,
+        count: u32,
+    }
+
+    fn totals(spans: &[SpanRecord]) -> Vec<SpanTotal> {
+        let mut totals: HashMap<(&'static str, &'static str), SpanTotal> = HashMap::new();
+        for span in spans {
+            let total = totals.entry((span.target, span.name)).or_insert(SpanTotal {
+                target: span.target,
+                name: span.name,
+                sum: Duration::default(),
+                max: Duration::default(),
+                count: 0,
+            });
+            total.sum += span.duration;
+            total.max = total.max.max(span.duration);
+            total.count += 1;
+        }
+        let mut totals: Vec<_> = totals.into_values().collect();
+        totals.sort_by_key(|total| (total.target, total.name));
+        totals
+    }
+
+    /// A window with the average and longest duration of each kind of span
+    /// in a `SpanHistory`, and the most recent spans.
+    pub struct SpanView {
+        /// How many of the most recent spans to list.
+        pub recent: usize,
+    }
+
+    impl Default for SpanView {
+        fn default() -> SpanView {
+            SpanView { recent: 20 }
+        }
+    }
+
+    impl SpanView {
+        pub fn new() -> SpanView {
+            SpanView::default()
+        }
+
+        /// Draws the view in a window of its own.
+        pub fn window(&mut self, ui: &imgui::Ui, title: &imgui::ImStr, history: &SpanHistory) {
+            let window = imgui::Window::new(title)
+                .position([0.0, 0.0], imgui::Condition::FirstUseEver)
+                .size([60.0, 20.0], imgui::Condition::FirstUseEver);
+            if let Some(window_token) = window.begin(ui) {
+                self.draw(ui, history);
+                window_token.end(ui);
+            }
+        }
+
+        /// Draws the view into the current window.
+        pub fn draw(&mut self, ui: &imgui::Ui, history: &SpanHistory) {
+            let spans = history.spans();
+            let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+            ui.text(format!("{:32} {:>9} {:>9} {:>6}", "span", "avg ms", "max ms", "count"));
+            for total in totals(&spans) {
+                ui.text(format!(
+                    "{:32} {:9.3} {:9.3} {:6}",
+                    format!("{}::{}", total.target, total.name),
+                    ms(total.sum) / total.count as f64,
+                    ms(total.max),
+                    total.count
+                ));
+            }
+
+            ui.separator();
+            for span in spans.iter().rev().take(self.recent) {
+                let frame = span.frame.map(|f| format!("#{} ", f)).unwrap_or_default();
+                ui.text(format!(
+                    "{}{}::{} {:.3} ms {}",
+                    frame,
+                    span.target,
+                    span.name,
+                    ms(span.duration),
+                    span.fields
+                ));
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tracing_crate::{debug_span, subscriber};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        fn span(name: &'static str, ms: u64) -> SpanRecord {
+            SpanRecord {
+                name,
+                target: "imtui",
+                frame: None,
+                fields: String::new(),
+                duration: Duration::from_millis(ms),
+            }
+        }
+
+        #[test]
+        fn keeps_the_most_recent_spans() {
+            let history = SpanHistory::new(2);
+            history.push(span("a", 1));
+            history.push(span("b", 2));
+            history.push(span("c", 3));
+            let names: Vec<&str> = history.spans().iter().map(|span| span.name).collect();
+            assert_eq!(names, vec!["b", "c"]);
+
+            let empty = SpanHistory::new(0);
+            empty.push(span("a", 1));
+            assert!(empty.spans().is_empty());
+        }
+
+        #[test]
+        fn totals_spans_by_name() {
+            let spans = [span("rasterize", 2), span("build", 5), span("rasterize", 4), span("build", 1)];
+            let totals = totals(&spans);
+            let ms = Duration::from_millis;
+            assert_eq!(
+                totals,
+                vec![
+                    SpanTotal { target: "imtui", name: "build", sum: ms(6), max: ms(5), count: 2 },
+                    SpanTotal { target: "imtui", name: "rasterize", sum: ms(6), max: ms(4), count: 2 },
+                ]
+            );
+        }
+
+        #[test]
+        fn records_closed_spans_with_their_fields() {
+            let (layer, history) = SpanLayer::new(8);
+            let subscriber = tracing_subscriber::registry().with(layer);
+            subscriber::with_default(subscriber, || {
+                let _outer = debug_span!(target: "imtui", "new_frame", frame = 7u64).entered();
+                let _inner = debug_span!(target: "app", "load", path = "a.txt").entered();
+            });
+            let spans = history.spans();
+            assert_eq!(spans.len(), 2);
+            assert_eq!((spans[0].target, spans[0].name, &*spans[0].fields), ("app", "load", "path=\"a.txt\""));
+            assert_eq!((spans[1].name, spans[1].frame), ("new_frame", Some(7)));
+            assert!(spans[1].fields.is_empty());
+        }
+    }
+}
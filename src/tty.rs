@@ -0,0 +1,213 @@
+//! Raw access to a terminal device, for backends that don't go through
+//! ncurses.
+
+use crate::clipboard::ClipboardLink;
+use libc;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+
+/// Where a backend finds the terminal it draws on and reads input from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TtySource {
+    /// stdin and stdout.
+    Stdio,
+    /// The controlling terminal, `/dev/tty`. This keeps working when stdin
+    /// and stdout are redirected, e.g. in `cmd | picker | xargs ...`, so the
+    /// app can read its stdin and write its stdout while the UI runs.
+    ControllingTerminal,
+    /// A terminal device such as a pty.
+    Path(PathBuf),
+    /// A file descriptor open for reading and writing. It isn't closed by
+    /// the backend.
+    Fd(RawFd),
+}
+
+impl Default for TtySource {
+    fn default() -> TtySource {
+        TtySource::Stdio
+    }
+}
+
+/// The file descriptors of an opened `TtySource`.
+pub(crate) struct TtyFds {
+    pub input: RawFd,
+    pub output: RawFd,
+    /// Keeps an opened device open for as long as it is used.
+    _file: Option<File>,
+}
+
+impl TtySource {
+    pub(crate) fn open(&self) -> io::Result<TtyFds> {
+        let path = match self {
+            TtySource::Stdio => {
+                return Ok(TtyFds {
+                    input: libc::STDIN_FILENO,
+                    output: libc::STDOUT_FILENO,
+                    _file: None,
+                })
+            }
+            TtySource::Fd(fd) => {
+                return Ok(TtyFds {
+                    input: *fd,
+                    output: *fd,
+                    _file: None,
+                })
+            }
+            TtySource::ControllingTerminal => PathBuf::from("/dev/tty"),
+            TtySource::Path(path) => path.clone(),
+        };
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(TtyFds {
+            input: file.as_raw_fd(),
+            output: file.as_raw_fd(),
+            _file: Some(file),
+        })
+    }
+}
+
+/// Waits up to `timeout_ms` for any of `fds` to become readable, and returns
+/// which of them are. A closed peer counts as readable, so the next read
+/// reports it.
+pub(crate) fn poll_readable(fds: &[RawFd], timeout_ms: i32) -> Vec<bool> {
+    let mut polled: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    let ready = unsafe { libc::poll(polled.as_mut_ptr(), polled.len() as libc::nfds_t, timeout_ms) };
+    polled
+        .iter()
+        .map(|fd| ready > 0 && fd.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0)
+        .collect()
+}
+
+/// Reads all input on `fd` that is immediately available.
+fn read_available(fd: RawFd, buf: &mut Vec<u8>) -> io::Result<()> {
+    let mut chunk = [0u8; 1024];
+    while poll_readable(&[fd], 0)[0] {
+        let n = unsafe { libc::read(fd, chunk.as_mut_ptr() as *mut libc::c_void, chunk.len()) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n as usize]);
+    }
+    Ok(())
+}
+
+fn write_all(fd: RawFd, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        let n = unsafe { libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        bytes = &bytes[n as usize..];
+    }
+    Ok(())
+}
+
+/// A terminal switched to raw mode, read from `input` and written to
+/// `output`. The original mode is restored on drop.
+pub(crate) struct Tty {
+    input: RawFd,
+    output: RawFd,
+    original: libc::termios,
+}
+
+impl Tty {
+    pub fn new(input: RawFd, output: RawFd) -> io::Result<Tty> {
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(input, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+        }
+        // Keep output post-processing, so "\n" still returns the carriage in
+        // whatever is printed after the UI is done.
+        raw.c_oflag |= libc::OPOST;
+        if unsafe { libc::tcsetattr(input, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Tty {
+            input,
+            output,
+            original,
+        })
+    }
+
+    /// Returns the size of the terminal in columns and rows.
+    pub fn size(&self) -> io::Result<(u16, u16)> {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(self.output, libc::TIOCGWINSZ, &mut size) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((size.ws_col, size.ws_row))
+    }
+
+    /// Waits up to `timeout_ms` for input. Returns true if there is some.
+    pub fn poll(&self, timeout_ms: i32) -> bool {
+        poll_readable(&[self.input], timeout_ms)[0]
+    }
+
+    /// Reads all input that is immediately available.
+    pub fn read_input(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        read_available(self.input, buf)
+    }
+
+    pub fn write_all(&self, bytes: &[u8]) -> io::Result<()> {
+        write_all(self.output, bytes)
+    }
+
+    /// A handle on the same terminal for the clipboard, which has to wait
+    /// for the replies to OSC 52 reads.
+    pub fn link(&self) -> TtyLink {
+        TtyLink {
+            input: self.input,
+            output: self.output,
+        }
+    }
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.input, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// The file descriptors of a `Tty`, for the clipboard. It must not outlive
+/// the terminal.
+pub(crate) struct TtyLink {
+    input: RawFd,
+    output: RawFd,
+}
+
+impl ClipboardLink for TtyLink {
+    fn send(&mut self, bytes: &[u8]) {
+        let _ = write_all(self.output, bytes);
+    }
+
+    fn read_input(&mut self, buf: &mut Vec<u8>) {
+        let _ = read_available(self.input, buf);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::input::Event;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+fn frame_step(fps: f32) -> Option<Duration> {
+    if fps > 0.0 {
+        Some(Duration::from_secs_f32(1.0 / fps))
+    } else {
+        None
+    }
+}
+
+/// Paces frames at the active or idle frame rate, cutting the wait short as
+/// soon as input arrives.
+///
+/// A non-positive active rate draws frames as fast as they come. A
+/// non-positive idle rate only draws an idle frame once there is input.
+pub(crate) struct VSync {
+    active_step: Option<Duration>,
+    idle_step: Option<Duration>,
+    last_frame: Instant,
+}
+
+impl VSync {
+    pub fn new(active_fps: f32, idle_fps: f32) -> VSync {
+        VSync {
+            active_step: frame_step(active_fps),
+            idle_step: frame_step(idle_fps),
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn wait<F: FnMut() -> bool>(&mut self, active: bool, mut input_pending: F) {
+        let step = if active {
+            match self.active_step {
+                Some(step) => Some(step),
+                None => {
+                    self.last_frame = Instant::now();
+                    return;
+                }
+            }
+        } else {
+            self.idle_step
+        };
+
+        loop {
+            let now = Instant::now();
+            let remaining = match step {
+                Some(step) => {
+                    let next_frame = self.last_frame + step;
+                    if now >= next_frame {
+                        break;
+                    }
+                    next_frame - now
+                }
+                None => POLL_INTERVAL,
+            };
+            if input_pending() {
+                break;
+            }
+            thread::sleep(remaining.min(POLL_INTERVAL));
+        }
+        self.last_frame = Instant::now();
+    }
+}
+
+/// How long after the last input frames keep being drawn at the active rate.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Decides whether frames are drawn at the active or idle rate: the active
+/// rate is used while the terminal is focused and there was input within the
+/// idle timeout, or while the app asked for it.
+pub(crate) struct Activity {
+    pub forced: bool,
+    pub focused: bool,
+    pub idle_timeout: Duration,
+    last_input: Instant,
+}
+
+impl Activity {
+    pub fn new() -> Activity {
+        Activity {
+            forced: false,
+            focused: true,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            last_input: Instant::now(),
+        }
+    }
+
+    /// Records the input of a frame, including focus changes.
+    pub fn update(&mut self, had_input: bool, events: &[Event]) {
+        if had_input {
+            self.last_input = Instant::now();
+        }
+        for event in events {
+            match event {
+                Event::FocusGained => self.focused = true,
+                Event::FocusLost => self.focused = false,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.focused && (self.forced || self.last_input.elapsed() < self.idle_timeout)
+    }
+}
@@ -0,0 +1,280 @@
+//! Widgets for terminal UIs that imgui doesn't have.
+
+use crate::input::{Event, Key, KeyEvent};
+use imgui;
+
+/// Something the user did to a `ListView`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListEvent {
+    /// The selection moved to this item.
+    Selected(usize),
+    /// The item was chosen with Enter or a double click.
+    Activated(usize),
+}
+
+/// A list that draws only the items that fit in the window, so it stays fast
+/// for any number of items.
+///
+/// Items can be several rows tall. `handle_events` moves the selection with
+/// j/k or the arrows, PageUp/PageDown, Home/End and Enter; call it only for
+/// the list that has the focus, and not while imgui's `io().want_text_input`
+/// is set as of the previous frame, or typing j or k in a text field moves
+/// the selection. `draw` selects items that are clicked, scrolls with the
+/// mouse wheel, and keeps the selection in view.
+#[derive(Clone, Debug)]
+pub struct ListView {
+    selected: Option<usize>,
+    /// The first item drawn.
+    top: usize,
+    /// How many items fit in the last frame, the distance PageUp and
+    /// PageDown move.
+    page: usize,
+    /// Set when the selection moved and must be scrolled into view.
+    follow: bool,
+    /// Draws the selected item in inverse colors while true, e.g. while the
+    /// window has the focus.
+    pub highlight: bool,
+}
+
+impl Default for ListView {
+    fn default() -> ListView {
+        ListView {
+            selected: None,
+            top: 0,
+            page: 1,
+            follow: false,
+            highlight: true,
+        }
+    }
+}
+
+impl ListView {
+    pub fn new() -> ListView {
+        ListView::default()
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects an item and scrolls it into view on the next `draw`.
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+        self.follow = true;
+    }
+
+    /// The first item drawn.
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Moves the selection for the list's keys. `len` is the number of items.
+    pub fn handle_events(&mut self, events: &[Event], len: usize) -> Vec<ListEvent> {
+        let mut list_events = vec![];
+        if len == 0 {
+            return list_events;
+        }
+        for event in events {
+            let key = match event {
+                Event::Key(KeyEvent { key, modifiers }) if !modifiers.alt && !modifiers.ctrl => *key,
+                _ => continue,
+            };
+            let current = self.selected.map(|i| i.min(len - 1));
+            let last = len - 1;
+            let target = match key {
+                Key::Down | Key::Char('j') => current.map_or(0, |i| (i + 1).min(last)),
+                Key::Up | Key::Char('k') => current.map_or(0, |i| i.saturating_sub(1)),
+                Key::PageDown => current.map_or(0, |i| (i + self.page).min(last)),
+                Key::PageUp => current.map_or(0, |i| i.saturating_sub(self.page)),
+                Key::Home => 0,
+                Key::End => last,
+                Key::Enter => {
+                    if let Some(index) = current {
+                        list_events.push(ListEvent::Activated(index));
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if Some(target) != self.selected {
+                self.select(Some(target));
+                list_events.push(ListEvent::Selected(target));
+            }
+        }
+        list_events
+    }
+
+    /// Draws the items that fit in the rest of the current window.
+    /// `height(index)` is the number of rows an item takes, and
+    /// `draw_item(ui, index, selected)` draws it in that many rows.
+    pub fn draw<H, F>(&mut self, ui: &imgui::Ui, len: usize, height: H, mut draw_item: F) -> Vec<ListEvent>
+    where
+        H: Fn(usize) -> u16,
+        F: FnMut(&imgui::Ui, usize, bool),
+    {
+        let mut list_events = vec![];
+        if len == 0 {
+            self.top = 0;
+            self.selected = None;
+            return list_events;
+        }
+        if let Some(selected) = self.selected {
+            self.selected = Some(selected.min(len - 1));
+        }
+        let available = ui.content_region_avail()[1].max(1.0) as usize;
+        let height = |index: usize| (height(index) as usize).max(1);
+
+        if ui.is_window_hovered() {
+            let wheel = ui.io().mouse_wheel;
+            if wheel > 0.0 {
+                self.top = self.top.saturating_sub(wheel.ceil() as usize);
+            } else if wheel < 0.0 {
+                self.top += (-wheel).ceil() as usize;
+            }
+        }
+        self.top = self.top.min(len - 1);
+
+        if self.follow {
+            self.follow = false;
+            if let Some(selected) = self.selected {
+                if selected < self.top {
+                    self.top = selected;
+                } else {
+                    // Scroll down just far enough for the selection to fit.
+                    let mut rows = height(selected);
+                    let mut top = selected;
+                    while top > self.top && rows + height(top - 1) <= available {
+                        top -= 1;
+                        rows += height(top);
+                    }
+                    self.top = top;
+                }
+            }
+        }
+
+        // Don't leave empty rows at the end while earlier items could fill
+        // them.
+        let mut rows = 0;
+        for index in self.top..len {
+            if rows + height(index) > available {
+                break;
+            }
+            rows += height(index);
+        }
+        while self.top > 0 && rows + height(self.top - 1) <= available {
+            self.top -= 1;
+            rows += height(self.top);
+        }
+
+        let style = ui.clone_style();
+        let width = ui.content_region_avail()[0];
+        let mut used = 0;
+        let mut index = self.top;
+        while index < len {
+            let item_height = height(index);
+            if used > 0 && used + item_height > available {
+                break;
+            }
+            let selected = self.selected == Some(index);
+            let highlighted = selected && self.highlight;
+            let colors = if highlighted {
+                let p0 = ui.cursor_screen_pos();
+                let p1 = [p0[0] + width, p0[1] + item_height as f32];
+                ui.get_window_draw_list()
+                    .add_rect(p0, p1, style[imgui::StyleColor::Text])
+                    .filled(true)
+                    .build();
+                Some(ui.push_style_color(imgui::StyleColor::Text, style[imgui::StyleColor::WindowBg]))
+            } else {
+                None
+            };
+
+            let start = ui.cursor_pos()[1];
+            ui.group(|| draw_item(ui, index, selected));
+            let hovered = ui.is_item_hovered();
+            if let Some(colors) = colors {
+                colors.pop(ui);
+            }
+            // Keep every item exactly as tall as it said.
+            let drawn = (ui.cursor_pos()[1] - start).max(0.0) as usize;
+            for _ in drawn..item_height {
+                ui.text("");
+            }
+
+            if hovered && ui.is_mouse_clicked(imgui::MouseButton::Left) && !selected {
+                self.selected = Some(index);
+                list_events.push(ListEvent::Selected(index));
+            }
+            if hovered && ui.is_mouse_double_clicked(imgui::MouseButton::Left) {
+                list_events.push(ListEvent::Activated(index));
+            }
+
+            used += item_height;
+            index += 1;
+        }
+        self.page = (index - self.top).max(1);
+        list_events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Modifiers;
+
+    fn keys(keys: &[Key]) -> Vec<Event> {
+        keys.iter()
+            .map(|&key| Event::Key(KeyEvent { key, modifiers: Modifiers::default() }))
+            .collect()
+    }
+
+    fn list_with_page(page: usize) -> ListView {
+        let mut list = ListView::new();
+        list.page = page;
+        list
+    }
+
+    #[test]
+    fn pages_down_to_the_end() {
+        let mut list = list_with_page(5);
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 12), vec![ListEvent::Selected(0)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 12), vec![ListEvent::Selected(5)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 12), vec![ListEvent::Selected(10)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 12), vec![ListEvent::Selected(11)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 12), vec![]);
+        assert_eq!(list.selected(), Some(11));
+    }
+
+    #[test]
+    fn pages_up_to_the_start() {
+        let mut list = list_with_page(5);
+        list.select(Some(7));
+        assert_eq!(list.handle_events(&keys(&[Key::PageUp]), 12), vec![ListEvent::Selected(2)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageUp]), 12), vec![ListEvent::Selected(0)]);
+        assert_eq!(list.handle_events(&keys(&[Key::PageUp]), 12), vec![]);
+        assert_eq!(list.selected(), Some(0));
+    }
+
+    #[test]
+    fn home_end_and_enter() {
+        let mut list = list_with_page(5);
+        let events = list.handle_events(&keys(&[Key::End, Key::Enter, Key::Home, Key::Char('j')]), 12);
+        assert_eq!(
+            events,
+            vec![
+                ListEvent::Selected(11),
+                ListEvent::Activated(11),
+                ListEvent::Selected(0),
+                ListEvent::Selected(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_selection_past_the_end_is_clamped() {
+        let mut list = list_with_page(5);
+        list.select(Some(20));
+        assert_eq!(list.handle_events(&keys(&[Key::PageDown]), 3), vec![ListEvent::Selected(2)]);
+        assert_eq!(list.handle_events(&keys(&[Key::Down]), 0), vec![]);
+    }
+}